@@ -0,0 +1,153 @@
+//! Explaining a raw transaction, so what is about to be broadcast can be shown
+//! as more than hex.
+
+use crate::protocol::{self, BorkTxData};
+use crate::wallet::{script_to_addr, Utxo};
+use crate::Network;
+use bitcoin::{Script, Transaction};
+use chrono::{DateTime, Utc};
+use failure::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutputType {
+    P2pkh,
+    P2sh,
+    OpReturn,
+    Nonstandard,
+}
+impl OutputType {
+    fn of(script: &Script) -> Self {
+        if script.is_p2pkh() {
+            OutputType::P2pkh
+        } else if script.is_p2sh() {
+            OutputType::P2sh
+        } else if script.is_op_return() {
+            OutputType::OpReturn
+        } else {
+            OutputType::Nonstandard
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputInfo {
+    pub txid: String,
+    pub vout: u32,
+    pub sequence: u32,
+    /// known when the output spent is given
+    pub value: Option<u64>,
+    /// from the output spent if given, otherwise from the scriptSig
+    pub address: Option<String>,
+    /// the scriptSig carries valid signatures for `address`
+    pub signed: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputInfo {
+    pub value: u64,
+    #[serde(rename = "type")]
+    pub output_type: OutputType,
+    pub address: Option<String>,
+    /// hex data of an OP_RETURN
+    pub data: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TxInfo<'a> {
+    pub txid: String,
+    pub size: usize,
+    pub inputs: Vec<InputInfo>,
+    pub outputs: Vec<OutputInfo>,
+    /// known when the outputs spent by every input are given
+    pub fee: Option<u64>,
+    /// koinu (or the network's smallest unit) per byte
+    pub fee_rate: Option<f64>,
+    pub bork: Option<BorkTxData<'a>>,
+    /// every input is signed
+    pub signed: bool,
+}
+
+/// Explains the raw transaction `tx`. `prevouts` are the outputs it spends, as
+/// far as they are known; without them input values and the fee are unknown.
+/// A bork in it is taken to be from `time`, e.g. that of its block.
+pub fn inspect_tx<'a>(
+    tx: &[u8],
+    prevouts: &[Utxo],
+    time: &'a DateTime<Utc>,
+    network: Network,
+) -> Result<TxInfo<'a>, Error> {
+    let size = tx.len();
+    let tx: Transaction = bitcoin::consensus::deserialize(tx)?;
+
+    let inputs = tx
+        .input
+        .iter()
+        .enumerate()
+        .map(|(i, input)| {
+            let prevout = prevouts
+                .iter()
+                .find(|u| u.outpoint == input.previous_output);
+            let signer = protocol::p2pkh_signer(&tx, i, network)
+                .or_else(|| protocol::p2sh_multisig_signer(&tx, i, network));
+            let address = match prevout {
+                Some(u) => script_to_addr(&u.output.script_pubkey, network).ok(),
+                None => signer.clone(),
+            };
+            InputInfo {
+                txid: format!("{:x}", input.previous_output.txid),
+                vout: input.previous_output.vout,
+                sequence: input.sequence,
+                value: prevout.map(|u| u.output.value),
+                signed: signer.is_some() && signer == address,
+                address,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let outputs = tx
+        .output
+        .iter()
+        .map(|o| OutputInfo {
+            value: o.value,
+            output_type: OutputType::of(&o.script_pubkey),
+            address: script_to_addr(&o.script_pubkey, network).ok(),
+            data: if o.script_pubkey.is_op_return() {
+                protocol::op_return_data(&o.script_pubkey).map(hex::encode)
+            } else {
+                None
+            },
+        })
+        .collect::<Vec<_>>();
+
+    let input_value = inputs
+        .iter()
+        .try_fold(0u64, |acc, i| i.value.and_then(|v| acc.checked_add(v)));
+    let output_value = tx
+        .output
+        .iter()
+        .try_fold(0u64, |acc, o| acc.checked_add(o.value))
+        .ok_or(format_err!("output value overflow"))?;
+    let fee = match input_value {
+        Some(input_value) => Some(
+            input_value
+                .checked_sub(output_value)
+                .ok_or(format_err!("outputs exceed inputs"))?,
+        ),
+        None => None,
+    };
+
+    Ok(TxInfo {
+        txid: format!("{:x}", tx.txid()),
+        size,
+        signed: inputs.iter().all(|i| i.signed),
+        inputs,
+        outputs,
+        fee,
+        fee_rate: fee.map(|fee| fee as f64 / size as f64),
+        bork: protocol::parse_tx(tx, time, network),
+    })
+}
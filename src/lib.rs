@@ -6,10 +6,14 @@ extern crate failure;
 extern crate serde_derive;
 
 use failure::Error;
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::convert::TryFrom;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 
 mod big_array;
+mod inspect;
 #[macro_use]
 mod macros;
 mod protocol;
@@ -22,20 +26,157 @@ extern "C" {
 
 }
 
-pub use self::wallet::{ChildWallet, Wallet};
+pub use self::inspect::{inspect_tx, InputInfo, OutputInfo, OutputType, TxInfo};
+pub use self::wallet::{
+    combine_psbts, create_psbt, extract_psbt, finalize_psbt, is_encrypted, privkey_to_wif, sweep,
+    verify_message, wif_to_privkey, AccountDiscovery, Amount, BorkPlan, BuiltTx, ChangePolicy,
+    ChildNumber, ChildWallet, CoinSelection, DerivationPath, Fee, KeyWallet, MultisigWallet,
+    PlannedTx, Secret, Seed, Sender, Share, TxBuildError, TxOptions, Utxo, Wallet, WatchOnlyWallet,
+    DEFAULT_GAP_LIMIT,
+};
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Output {
     address: String,
-    value: u64,
+    value: Amount,
 }
 impl Output {
-    pub fn as_tup(&self) -> (&str, u64) {
+    pub fn as_tup(&self) -> (&str, Amount) {
         (self.address.as_str(), self.value)
     }
 }
 
+/// a hex funding transaction, or an unspent output as an explorer reports it
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Input {
+    Tx(String),
+    Utxo {
+        txid: String,
+        vout: u32,
+        value: Amount,
+        script: String,
+        #[serde(default)]
+        tx: Option<String>,
+    },
+}
+
+impl Input {
+    /// a funding transaction offers its outputs whose scripts `spendable`
+    /// accepts, an unspent output itself
+    fn into_utxos<F>(self, spendable: F) -> Result<Vec<Utxo>, Error>
+    where
+        F: Fn(&bitcoin::Script) -> bool,
+    {
+        match self {
+            Input::Tx(tx) => Ok(Utxo::outputs_of(&hex::decode(tx)?)?
+                .into_iter()
+                .filter(|u| spendable(&u.output.script_pubkey))
+                .collect()),
+            Input::Utxo {
+                txid,
+                vout,
+                value,
+                script,
+                tx,
+            } => {
+                let utxo = Utxo::new(&txid, vout, value.as_sat(), hex::decode(script)?.into())?;
+                Ok(vec![match tx {
+                    Some(tx) => utxo.with_tx(&hex::decode(tx)?)?,
+                    None => utxo,
+                }])
+            }
+        }
+    }
+}
+
+/// what `js_try!` throws: a JS error with the message and backtrace of `e`,
+/// and the details of a `TxBuildError` as its `txBuildError` property
+#[doc(hidden)]
+pub fn js_error(line: u32, e: Error) -> js_sys::Error {
+    let err = format_js_err!("{}: {}\n{}", line, e, e.backtrace());
+    if let Some(details) = e
+        .downcast_ref::<TxBuildError>()
+        .and_then(|e| JsValue::from_serde(e).ok())
+    {
+        let _ = js_sys::Reflect::set(&err, &"txBuildError".into(), &details);
+    }
+    err
+}
+
+/// the outputs `inputs` (an array of `Input`) offer to spend from any of
+/// `scripts`
+fn parse_inputs(inputs: JsValue, scripts: &[bitcoin::Script]) -> Result<Vec<Utxo>, Error> {
+    let mut utxos = Vec::new();
+    for input in inputs.into_serde::<Vec<Input>>()? {
+        utxos.extend(input.into_utxos(|s| scripts.contains(s))?);
+    }
+    Ok(utxos)
+}
+
+/// sweeps what `keys` can spend of `inputs` (an array of `Input`) to
+/// `destination`; returns a `JsBuiltTx`
+fn sweep_to(
+    keys: &[KeyWallet],
+    inputs: JsValue,
+    destination: &str,
+    fee: JsValue,
+    network: Network,
+) -> Result<JsValue, JsValue> {
+    let scripts = keys.iter().map(|k| k.script()).collect::<Vec<_>>();
+    let utxos = js_try!(parse_inputs(inputs, &scripts));
+    let options = TxOptions::new(js_try!(parse_fee(fee)));
+
+    let built = js_try!(sweep(keys, &utxos, destination, &options, network));
+    Ok(js_try!(JsValue::from_serde(&js_try!(
+        JsBuiltTx::from_built(&built)
+    ))))
+}
+
+/// moves everything the WIF `keys` can spend to `destination`, the fee taken
+/// from the total
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn sweepKeys(
+    keys: JsValue,
+    inputs: JsValue,
+    destination: String,
+    fee: JsValue,
+    network: Network,
+) -> Result<JsValue, JsValue> {
+    let keys = js_try!(js_try!(keys.into_serde::<Vec<String>>())
+        .iter()
+        .map(|wif| KeyWallet::from_wif(wif, network))
+        .collect::<Result<Vec<_>, _>>());
+    sweep_to(&keys, inputs, &destination, fee, network)
+}
+
+/// explains the hex transaction `tx` as a `TxInfo`; `inputs`, an optional
+/// array of `Input`, are the outputs it spends, and `time`, an RFC 3339 date
+/// as `Date.toISOString` gives, is when a bork in it was made
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn inspectTx(
+    tx: String,
+    inputs: JsValue,
+    time: String,
+    network: Network,
+) -> Result<JsValue, JsValue> {
+    let mut prevouts = Vec::new();
+    for input in js_try!(inputs.into_serde::<Option<Vec<Input>>>()).unwrap_or_default() {
+        prevouts.extend(js_try!(input.into_utxos(|_| true)));
+    }
+    let time: chrono::DateTime<chrono::Utc> = js_try!(time.parse());
+    let info = js_try!(inspect_tx(
+        &js_try!(hex::decode(tx)),
+        &prevouts,
+        &time,
+        network
+    ));
+    Ok(js_try!(JsValue::from_serde(&info)))
+}
+
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn processBlock(block: String, network: Network) -> Result<JsValue, JsValue> {
@@ -80,11 +221,287 @@ pub fn processBlock(block: String, network: Network) -> Result<JsValue, JsValue>
     Ok(js_try!(JsValue::from_serde(&borker_txs)))
 }
 
+/// negative indices are hardened, with -0 meaning 0'
+fn path_from_f64s(derivation_path: &[f64]) -> Result<DerivationPath, Error> {
+    Ok(DerivationPath::from(
+        derivation_path
+            .iter()
+            .map(|idx| {
+                ensure!(
+                    idx.fract() == 0.0 && idx.abs() < (1_u64 << 31) as f64,
+                    "invalid child index: {}",
+                    idx
+                );
+                ChildNumber::new(idx.abs() as u32, idx.is_sign_negative())
+            })
+            .collect::<Result<Vec<_>, Error>>()?,
+    ))
+}
+
+/// parses the arguments of a bork and passes its utxos, outputs and encoded
+/// OP_RETURN payloads to `build`
+fn new_bork<F, T>(
+    data: JsValue,
+    inputs: JsValue,
+    scripts: &[bitcoin::Script],
+    recipient: JsValue,
+    mentions: JsValue,
+    nonce: u8,
+    version: Option<u16>,
+    max_len: usize,
+    build: F,
+) -> Result<JsValue, JsValue>
+where
+    F: FnOnce(&[Utxo], &[(&str, Amount)], &[Vec<u8>]) -> Result<T, Error>,
+    T: serde::Serialize,
+{
+    use protocol::*;
+
+    let utxos = js_try!(parse_inputs(inputs, scripts));
+
+    let mut outputs = js_try!(recipient.into_serde::<Option<Output>>())
+        .into_iter()
+        .collect::<Vec<Output>>();
+    outputs.extend(js_try!(mentions.into_serde::<Vec<Output>>()));
+
+    let op_rets = js_try!(encode(
+        js_try!(NewBork::try_from(js_try!(data.into_serde::<NewBorkData>()))),
+        nonce,
+        version,
+        max_len,
+    ));
+    let res = js_try!(build(
+        &utxos,
+        &outputs.iter().map(|o| o.as_tup()).collect::<Vec<_>>(),
+        &op_rets,
+    ));
+
+    Ok(js_try!(JsValue::from_serde(&res)))
+}
+
+fn chain_to_hex(chain: Vec<BuiltTx>) -> Result<Vec<String>, Error> {
+    chain
+        .iter()
+        .map(|tx| tx.serialize().map(hex::encode))
+        .collect()
+}
+
+/// `fee` is either a flat `Amount` or a `Fee`
+fn parse_fee(fee: JsValue) -> Result<Fee, Error> {
+    if fee.is_object() {
+        Ok(fee.into_serde()?)
+    } else {
+        Ok(Fee::Flat(fee.into_serde::<Amount>()?.as_sat()))
+    }
+}
+
+/// `createPsbt` of every wallet spending outputs of `script`: `outputs` is an
+/// array of `Output`, `op_return` hex, and the psbt crosses the wasm boundary
+/// base64 encoded, as in Bitcoin Core's rpc
+fn new_psbt(
+    inputs: JsValue,
+    outputs: JsValue,
+    fee: JsValue,
+    op_return: Option<String>,
+    script: &bitcoin::Script,
+    script_sig_len: usize,
+    redeem_script: Option<&bitcoin::Script>,
+    network: Network,
+) -> Result<String, JsValue> {
+    let inputs = js_try!(inputs.into_serde::<Vec<String>>());
+    let inputs = js_try!(inputs
+        .into_iter()
+        .map(|i| hex::decode(i))
+        .collect::<Result<Vec<_>, _>>());
+    let outputs = js_try!(outputs.into_serde::<Vec<Output>>());
+    let op_return = match op_return {
+        Some(o) => Some(js_try!(hex::decode(o))),
+        None => None,
+    };
+
+    let psbt = js_try!(wallet::create_psbt(
+        &Sender {
+            scripts: std::slice::from_ref(script),
+            script_sig_len,
+            address: &js_try!(wallet::script_to_addr(script, network)),
+        },
+        redeem_script,
+        &inputs,
+        &outputs.iter().map(|o| o.as_tup()).collect::<Vec<_>>(),
+        op_return.as_ref().map(|o| o.as_slice()),
+        &TxOptions::new(js_try!(parse_fee(fee))),
+        network,
+    ));
+    Ok(base64::encode(&psbt))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsOutPoint {
+    txid: String,
+    vout: u32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsBuiltTx {
+    tx: String,
+    txid: String,
+    spent: Vec<JsOutPoint>,
+    fee: Amount,
+    change: Amount,
+    change_vout: Option<u32>,
+}
+impl JsBuiltTx {
+    fn from_built(built: &BuiltTx) -> Result<Self, Error> {
+        Ok(JsBuiltTx {
+            tx: hex::encode(built.serialize()?),
+            txid: built.txid(),
+            spent: built
+                .spent
+                .iter()
+                .map(|o| JsOutPoint {
+                    txid: format!("{:x}", o.txid),
+                    vout: o.vout,
+                })
+                .collect(),
+            fee: built.fee,
+            change: built.change,
+            change_vout: built.change_vout,
+        })
+    }
+}
+
+/// `options` is a `TxOptions`; returns a `JsBuiltTx`
+fn build_tx<F>(
+    inputs: JsValue,
+    scripts: &[bitcoin::Script],
+    outputs: JsValue,
+    op_return: Option<String>,
+    options: JsValue,
+    build: F,
+) -> Result<JsValue, JsValue>
+where
+    F: FnOnce(&[Utxo], &[(&str, Amount)], Option<&[u8]>, &TxOptions) -> Result<BuiltTx, Error>,
+{
+    let utxos = js_try!(parse_inputs(inputs, scripts));
+    let outputs = js_try!(outputs.into_serde::<Vec<Output>>());
+    let op_return = match op_return {
+        Some(o) => Some(js_try!(hex::decode(o))),
+        None => None,
+    };
+    let options = js_try!(options.into_serde::<TxOptions>());
+
+    let built = js_try!(build(
+        &utxos,
+        &outputs.iter().map(|o| o.as_tup()).collect::<Vec<_>>(),
+        op_return.as_ref().map(|o| o.as_slice()),
+        &options,
+    ));
+    Ok(js_try!(JsValue::from_serde(&js_try!(
+        JsBuiltTx::from_built(&built)
+    ))))
+}
+
+/// `chain` is an array of hex transactions, `inputs` of `Input` and `options`
+/// a `TxOptions`; returns an array of `JsBuiltTx`
+fn bump_fee<F>(
+    chain: JsValue,
+    inputs: JsValue,
+    scripts: &[bitcoin::Script],
+    options: JsValue,
+    bump: F,
+) -> Result<JsValue, JsValue>
+where
+    F: FnOnce(&[Vec<u8>], &[Utxo], &TxOptions) -> Result<Vec<BuiltTx>, Error>,
+{
+    let chain = js_try!(chain.into_serde::<Vec<String>>());
+    let chain = js_try!(chain
+        .into_iter()
+        .map(|tx| hex::decode(tx))
+        .collect::<Result<Vec<_>, _>>());
+    let utxos = js_try!(parse_inputs(inputs, scripts));
+    let options = js_try!(options.into_serde::<TxOptions>());
+
+    let built = js_try!(bump(&chain, &utxos, &options));
+    let built = js_try!(built
+        .iter()
+        .map(JsBuiltTx::from_built)
+        .collect::<Result<Vec<_>, _>>());
+    Ok(js_try!(JsValue::from_serde(&built)))
+}
+
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn combinePsbts(psbts: JsValue) -> Result<String, JsValue> {
+    let psbts = js_try!(psbts.into_serde::<Vec<String>>());
+    let psbts = js_try!(psbts
+        .into_iter()
+        .map(|p| base64::decode(p))
+        .collect::<Result<Vec<_>, _>>());
+    Ok(base64::encode(&js_try!(combine_psbts(&psbts))))
+}
+
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn finalizePsbt(psbt: String) -> Result<String, JsValue> {
+    Ok(base64::encode(&js_try!(finalize_psbt(&js_try!(
+        base64::decode(psbt)
+    )))))
+}
+
+/// the hex encoded network transaction of a finalized psbt
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn extractPsbt(psbt: String) -> Result<String, JsValue> {
+    Ok(hex::encode(js_try!(extract_psbt(&js_try!(
+        base64::decode(psbt)
+    )))))
+}
+
+/// the hex encoded OP_RETURN payloads of a bork, for building it as a psbt;
+/// each is as large as `network` relays by default, or 80 bytes
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn encodeBork(
+    data: JsValue,
+    nonce: u8,
+    version: Option<u16>,
+    network: Option<Network>,
+) -> Result<JsValue, JsValue> {
+    use protocol::*;
+
+    let op_rets = js_try!(encode(
+        js_try!(NewBork::try_from(js_try!(data.into_serde::<NewBorkData>()))),
+        nonce,
+        version,
+        network
+            .map(|n| wallet::max_relayed_op_return_len(wallet::data_carrier_size(n)))
+            .unwrap_or(80),
+    ));
+    Ok(js_try!(JsValue::from_serde(
+        &op_rets.into_iter().map(hex::encode).collect::<Vec<_>>()
+    )))
+}
+
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn verifyMessage(
+    address: String,
+    signature: String,
+    message: String,
+    network: Network,
+) -> Result<bool, JsValue> {
+    Ok(js_try!(verify_message(
+        &address, &signature, &message, network
+    )))
+}
+
 // JS Wrappers
 
 #[wasm_bindgen]
 pub struct JsWallet {
-    inner: Wallet,
+    inner: Rc<RefCell<Wallet>>,
 }
 
 #[wasm_bindgen]
@@ -93,20 +510,17 @@ impl JsWallet {
     pub fn new(words: Option<Vec<JsValue>>) -> Result<JsWallet, JsValue> {
         console_error_panic_hook::set_once();
         Ok(match words {
-            Some(w) => JsWallet {
-                inner: js_try!(Wallet::from_words(&js_try!(w
-                    .iter()
-                    .map(|a| a.into_serde::<String>().map_err(Error::from))
-                    .collect::<Result<Vec<String>, Error>>()))),
-            },
-            None => JsWallet {
-                inner: Wallet::new(),
-            },
+            Some(w) => JsWallet::from(js_try!(Wallet::from_words(&js_try!(w
+                .iter()
+                .map(|a| a.into_serde::<String>().map_err(Error::from))
+                .collect::<Result<Vec<String>, Error>>())))),
+            None => JsWallet::from(Wallet::new()),
         })
     }
 
     pub fn words(&self) -> Vec<JsValue> {
         self.inner
+            .borrow()
             .words()
             .iter()
             .map(|a| JsValue::from_serde(a).unwrap())
@@ -115,44 +529,391 @@ impl JsWallet {
 
     #[allow(non_snake_case)]
     pub fn childAt(&mut self, derivation_path: Vec<f64>) -> Result<JsChildWallet, JsValue> {
-        let mut cur: &mut ChildWallet = &mut self.inner.parent_mut();
+        let path = js_try!(path_from_f64s(&derivation_path));
+        self.child(path)
+    }
 
-        for idx in derivation_path {
-            cur = js_try!(cur.load_child(idx.abs() as u32, idx.is_sign_negative()))
-        }
-        Ok(JsChildWallet { inner: cur.clone() })
+    #[allow(non_snake_case)]
+    pub fn childAtPath(&mut self, derivation_path: String) -> Result<JsChildWallet, JsValue> {
+        let path: DerivationPath = js_try!(derivation_path.parse());
+        self.child(path)
+    }
+
+    pub fn discover(
+        &self,
+        network: Network,
+        seen: JsValue,
+        gap_limit: Option<u32>,
+    ) -> Result<JsValue, JsValue> {
+        let seen = js_try!(seen.into_serde::<HashSet<String>>());
+        let gap_limit = gap_limit.unwrap_or(DEFAULT_GAP_LIMIT);
+        let accounts = js_try!(self
+            .inner
+            .borrow()
+            .discover(network, gap_limit, |addr| seen.contains(addr)));
+        Ok(js_try!(JsValue::from_serde(&accounts)))
+    }
+
+    /// like `JsChildWallet.buildSigned` for the child at `sender`, also
+    /// spending outputs of the children at `paths`
+    #[allow(non_snake_case)]
+    pub fn buildSigned(
+        &self,
+        sender: String,
+        paths: JsValue,
+        inputs: JsValue,
+        outputs: JsValue,
+        op_return: Option<String>,
+        options: JsValue,
+        network: Network,
+    ) -> Result<JsValue, JsValue> {
+        let (sender, paths, scripts) = js_try!(self.signers(sender, paths));
+        build_tx(
+            inputs,
+            &scripts,
+            outputs,
+            op_return,
+            options,
+            |utxos, outputs, op_return, options| {
+                self.inner
+                    .borrow_mut()
+                    .build_signed(&sender, &paths, utxos, outputs, op_return, options, network)
+            },
+        )
+    }
+
+    /// like `JsChildWallet.newBork` for the child at `sender`, also spending
+    /// outputs of the children at `paths`
+    #[allow(non_snake_case)]
+    pub fn newBork(
+        &self,
+        sender: String,
+        paths: JsValue,
+        data: JsValue,
+        inputs: JsValue,
+        recipient: JsValue,
+        mentions: JsValue,
+        fee: JsValue,
+        network: Network,
+        version: Option<u16>,
+        rbf: Option<bool>,
+    ) -> Result<JsValue, JsValue> {
+        let (sender, paths, scripts) = js_try!(self.signers(sender, paths));
+        let options = TxOptions {
+            rbf: rbf.unwrap_or(false),
+            ..TxOptions::new(js_try!(parse_fee(fee)))
+        };
+        let nonce = js_try!(self.inner.borrow_mut().derive(&sender))
+            .key()
+            .next_nonce();
+        let res = new_bork(
+            data,
+            inputs,
+            &scripts,
+            recipient,
+            mentions,
+            nonce,
+            version,
+            options.max_op_return_len(network),
+            |utxos, outputs, op_rets| {
+                chain_to_hex(
+                    self.inner
+                        .borrow_mut()
+                        .sign_bork(&sender, &paths, utxos, outputs, op_rets, &options, network)?,
+                )
+            },
+        )?;
+        // the nonce is used up only once the bork is signed
+        js_try!(self.inner.borrow_mut().derive(&sender))
+            .key_mut()
+            .nonce();
+        Ok(res)
+    }
+
+    /// like `sweepKeys`, for the children at `paths`, e.g. an old
+    /// derivation path
+    pub fn sweep(
+        &self,
+        paths: JsValue,
+        inputs: JsValue,
+        destination: String,
+        fee: JsValue,
+        network: Network,
+    ) -> Result<JsValue, JsValue> {
+        let paths = js_try!(paths.into_serde::<Vec<String>>());
+        let mut wallet = self.inner.borrow_mut();
+        let keys = js_try!(paths
+            .iter()
+            .map(|path| Ok(wallet.derive(&path.parse()?)?.to_key_wallet()))
+            .collect::<Result<Vec<_>, Error>>());
+        sweep_to(&keys, inputs, &destination, fee, network)
     }
 
     #[allow(non_snake_case)]
     pub fn toBuffer(&self) -> Result<String, JsValue> {
-        Ok(hex::encode(js_try!(self.inner.as_bytes())))
+        Ok(hex::encode(js_try!(self.inner.borrow().as_bytes())))
     }
 
     #[allow(non_snake_case)]
     pub fn fromBuffer(buffer: String) -> Result<JsWallet, JsValue> {
-        Ok(JsWallet {
-            inner: js_try!(Wallet::from_bytes(&js_try!(hex::decode(&buffer)))),
+        Ok(JsWallet::from(js_try!(Wallet::from_bytes(&js_try!(
+            hex::decode(&buffer)
+        )))))
+    }
+
+    #[allow(non_snake_case)]
+    pub fn toEncryptedBuffer(&self, password: String) -> Result<String, JsValue> {
+        Ok(hex::encode(js_try!(self
+            .inner
+            .borrow()
+            .as_encrypted_bytes(&password))))
+    }
+
+    #[allow(non_snake_case)]
+    pub fn fromEncryptedBuffer(buffer: String, password: String) -> Result<JsWallet, JsValue> {
+        Ok(JsWallet::from(js_try!(Wallet::from_encrypted_bytes(
+            &js_try!(hex::decode(&buffer)),
+            &password
+        ))))
+    }
+
+    #[allow(non_snake_case)]
+    pub fn isEncryptedBuffer(buffer: String) -> Result<bool, JsValue> {
+        Ok(is_encrypted(&js_try!(hex::decode(&buffer))))
+    }
+
+    /// re-encodes a plaintext buffer from `toBuffer` as an encrypted one
+    #[allow(non_snake_case)]
+    pub fn encryptBuffer(buffer: String, password: String) -> Result<String, JsValue> {
+        JsWallet::fromBuffer(buffer)?.toEncryptedBuffer(password)
+    }
+}
+impl JsWallet {
+    /// the derivation paths `sender` and `paths` (an array of strings), and
+    /// the scripts of their children
+    fn signers(
+        &self,
+        sender: String,
+        paths: JsValue,
+    ) -> Result<(DerivationPath, Vec<DerivationPath>, Vec<bitcoin::Script>), Error> {
+        let sender: DerivationPath = sender.parse()?;
+        let paths = paths
+            .into_serde::<Vec<String>>()?
+            .iter()
+            .map(|p| p.parse())
+            .collect::<Result<Vec<DerivationPath>, Error>>()?;
+        let mut wallet = self.inner.borrow_mut();
+        let scripts = std::iter::once(&sender)
+            .chain(&paths)
+            .map(|path| Ok(wallet.derive(path)?.script()))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok((sender, paths, scripts))
+    }
+
+    /// children share the wallet, so nonce counters they advance are saved by
+    /// `toBuffer`
+    fn child(&self, path: DerivationPath) -> Result<JsChildWallet, JsValue> {
+        js_try!(self.inner.borrow_mut().derive(&path));
+        Ok(JsChildWallet {
+            wallet: self.inner.clone(),
+            path,
         })
     }
 }
+impl From<Wallet> for JsWallet {
+    fn from(wallet: Wallet) -> Self {
+        JsWallet {
+            inner: Rc::new(RefCell::new(wallet)),
+        }
+    }
+}
 
 #[wasm_bindgen]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Network {
     Dogecoin,
     Litecoin,
     Bitcoin,
 }
+impl Network {
+    /// the unit amounts are written in
+    pub fn ticker(&self) -> &'static str {
+        match self {
+            Network::Dogecoin => "DOGE",
+            Network::Litecoin => "LTC",
+            Network::Bitcoin => "BTC",
+        }
+    }
+
+    /// SLIP-44 coin type
+    pub fn coin_type(&self) -> u32 {
+        match self {
+            Network::Dogecoin => 3,
+            Network::Litecoin => 2,
+            Network::Bitcoin => 0,
+        }
+    }
+}
+
+/// `newBork` of `JsChildWallet` and `JsKeyWallet`; `rbf` signals the chain
+/// may be replaced by `bumpFee`
+fn key_new_bork(
+    key: &mut KeyWallet,
+    data: JsValue,
+    inputs: JsValue,
+    recipient: JsValue,
+    mentions: JsValue,
+    fee: JsValue,
+    network: Network,
+    version: Option<u16>,
+    rbf: Option<bool>,
+) -> Result<JsValue, JsValue> {
+    let options = TxOptions {
+        rbf: rbf.unwrap_or(false),
+        ..TxOptions::new(js_try!(parse_fee(fee)))
+    };
+    let res = new_bork(
+        data,
+        inputs,
+        &[key.script()],
+        recipient,
+        mentions,
+        key.next_nonce(),
+        version,
+        options.max_op_return_len(network),
+        |utxos, outputs, op_rets| {
+            chain_to_hex(key.sign_bork(utxos, outputs, op_rets, &options, network)?)
+        },
+    )?;
+    // the nonce is used up only once the bork is signed
+    key.nonce();
+    Ok(res)
+}
+
+/// `planBork`: the transactions `newBork` would sign, with their fees and
+/// change, as a `BorkPlan`
+fn key_plan_bork(
+    key: &KeyWallet,
+    data: JsValue,
+    inputs: JsValue,
+    recipient: JsValue,
+    mentions: JsValue,
+    fee: JsValue,
+    network: Network,
+    version: Option<u16>,
+) -> Result<JsValue, JsValue> {
+    let options = TxOptions::new(js_try!(parse_fee(fee)));
+    new_bork(
+        data,
+        inputs,
+        &[key.script()],
+        recipient,
+        mentions,
+        key.next_nonce(),
+        version,
+        options.max_op_return_len(network),
+        |utxos, outputs, op_rets| key.plan_bork(utxos, outputs, op_rets, &options, network),
+    )
+}
+
+/// `constructSigned`: a hex transaction paying `amount` to `destination`
+fn key_construct_signed(
+    key: &KeyWallet,
+    inputs: JsValue,
+    destination: String,
+    amount: JsValue,
+    fee: JsValue,
+    network: Network,
+) -> Result<String, JsValue> {
+    let utxos = js_try!(parse_inputs(inputs, &[key.script()]));
+    let options = TxOptions::new(js_try!(parse_fee(fee)));
+    let amount = js_try!(amount.into_serde::<Amount>());
+
+    let signed = js_try!(key.build_signed(
+        &utxos,
+        &[(destination.as_str(), amount)],
+        None,
+        &options,
+        network
+    ));
+    Ok(hex::encode(js_try!(signed.serialize())))
+}
+
+/// `buildSigned`: like `constructSigned`, with several outputs and
+/// `TxOptions`, also returning the outpoints spent
+fn key_build_signed(
+    key: &KeyWallet,
+    inputs: JsValue,
+    outputs: JsValue,
+    op_return: Option<String>,
+    options: JsValue,
+    network: Network,
+) -> Result<JsValue, JsValue> {
+    build_tx(
+        inputs,
+        &[key.script()],
+        outputs,
+        op_return,
+        options,
+        |utxos, outputs, op_return, options| {
+            key.build_signed(utxos, outputs, op_return, options, network)
+        },
+    )
+}
+
+/// `bumpFee`: replaces `chain`, hex transactions from `newBork` or
+/// `buildSigned` that signalled RBF, with ones paying the fee in `options`;
+/// `inputs` are what the first spends
+fn key_bump_fee(
+    key: &KeyWallet,
+    chain: JsValue,
+    inputs: JsValue,
+    options: JsValue,
+    network: Network,
+) -> Result<JsValue, JsValue> {
+    bump_fee(
+        chain,
+        inputs,
+        &[key.script()],
+        options,
+        |chain, utxos, options| key.bump_fee(chain, utxos, options, network),
+    )
+}
+
+/// `cpfp`: a `JsBuiltTx` spending the change of `parent` so the two together
+/// pay the fee in `options`
+fn key_cpfp(
+    key: &KeyWallet,
+    parent: String,
+    parent_fee: JsValue,
+    options: JsValue,
+    network: Network,
+) -> Result<JsValue, JsValue> {
+    let parent = js_try!(hex::decode(parent));
+    let parent_fee = js_try!(parent_fee.into_serde::<Amount>());
+    let options = js_try!(options.into_serde::<TxOptions>());
+    let built = js_try!(key.cpfp(&parent, parent_fee, &options, network));
+    Ok(js_try!(JsValue::from_serde(&js_try!(
+        JsBuiltTx::from_built(&built)
+    ))))
+}
+
+/// `signPsbt`, base64 in and out
+fn key_sign_psbt(key: &KeyWallet, psbt: String) -> Result<String, JsValue> {
+    let psbt = js_try!(base64::decode(psbt));
+    Ok(base64::encode(&js_try!(key.sign_psbt(&psbt))))
+}
 
 #[wasm_bindgen]
 pub struct JsChildWallet {
-    inner: ChildWallet,
+    wallet: Rc<RefCell<Wallet>>,
+    path: DerivationPath,
 }
 
 #[wasm_bindgen]
 impl JsChildWallet {
     pub fn address(&self, network: Network) -> String {
-        self.inner.address(network)
+        self.with_key(|k| k.address(network))
     }
 
     #[allow(non_snake_case)]
@@ -162,53 +923,222 @@ impl JsChildWallet {
         inputs: JsValue,
         recipient: JsValue,
         mentions: JsValue,
-        fee: f64,
+        fee: JsValue,
         network: Network,
         version: Option<u16>,
+        rbf: Option<bool>,
     ) -> Result<JsValue, JsValue> {
-        use protocol::*;
+        self.with_key(|k| {
+            key_new_bork(
+                k, data, inputs, recipient, mentions, fee, network, version, rbf,
+            )
+        })
+    }
 
-        let inputs = js_try!(inputs.into_serde::<Vec<String>>());
-        let inputs = js_try!(inputs
-            .into_iter()
-            .map(|i| hex::decode(i))
-            .collect::<Result<Vec<_>, _>>());
+    #[allow(non_snake_case)]
+    pub fn planBork(
+        &self,
+        data: JsValue,
+        inputs: JsValue,
+        recipient: JsValue,
+        mentions: JsValue,
+        fee: JsValue,
+        network: Network,
+        version: Option<u16>,
+    ) -> Result<JsValue, JsValue> {
+        self.with_key(|k| {
+            key_plan_bork(k, data, inputs, recipient, mentions, fee, network, version)
+        })
+    }
 
-        let mut outputs = js_try!(recipient.into_serde::<Option<Output>>())
-            .into_iter()
-            .collect::<Vec<Output>>();
-        outputs.extend(js_try!(mentions.into_serde::<Vec<Output>>()));
+    #[allow(non_snake_case)]
+    pub fn constructSigned(
+        &self,
+        inputs: JsValue,
+        destination: String,
+        amount: JsValue,
+        fee: JsValue,
+        network: Network,
+    ) -> Result<String, JsValue> {
+        self.with_key(|k| key_construct_signed(k, inputs, destination, amount, fee, network))
+    }
 
-        let op_rets = js_try!(encode(
-            js_try!(NewBork::try_from(js_try!(data.into_serde::<NewBorkData>()))),
-            self.inner.nonce(),
-            version,
-        ));
-        let mut txs = vec![];
-        let mut prev_tx: Option<Vec<u8>> = None;
-        let mut o = outputs.iter().map(|o| o.as_tup()).collect::<Vec<_>>();
-        for op_ret in op_rets {
-            let tx = js_try!(self.inner.construct_signed(
-                match prev_tx {
-                    Some(t) => vec![t.clone()],
-                    None => inputs.clone(),
-                }
-                .as_slice(),
-                &o,
-                fee as u64,
-                Some(op_ret.as_slice()),
+    #[allow(non_snake_case)]
+    pub fn buildSigned(
+        &self,
+        inputs: JsValue,
+        outputs: JsValue,
+        op_return: Option<String>,
+        options: JsValue,
+        network: Network,
+    ) -> Result<JsValue, JsValue> {
+        self.with_key(|k| key_build_signed(k, inputs, outputs, op_return, options, network))
+    }
+
+    #[allow(non_snake_case)]
+    pub fn bumpFee(
+        &self,
+        chain: JsValue,
+        inputs: JsValue,
+        options: JsValue,
+        network: Network,
+    ) -> Result<JsValue, JsValue> {
+        self.with_key(|k| key_bump_fee(k, chain, inputs, options, network))
+    }
+
+    #[allow(non_snake_case)]
+    pub fn cpfp(
+        &self,
+        parent: String,
+        parent_fee: JsValue,
+        options: JsValue,
+        network: Network,
+    ) -> Result<JsValue, JsValue> {
+        self.with_key(|k| key_cpfp(k, parent, parent_fee, options, network))
+    }
+
+    #[allow(non_snake_case)]
+    pub fn createPsbt(
+        &self,
+        inputs: JsValue,
+        outputs: JsValue,
+        fee: JsValue,
+        op_return: Option<String>,
+        network: Network,
+    ) -> Result<String, JsValue> {
+        self.with_key(|k| {
+            new_psbt(
+                inputs,
+                outputs,
+                fee,
+                op_return,
+                &k.script(),
+                k.script_sig_len(),
+                None,
                 network,
-            ));
-            prev_tx = Some(tx.clone());
-            txs.push(tx);
-            o = Vec::new();
+            )
+        })
+    }
+
+    #[allow(non_snake_case)]
+    pub fn signPsbt(&self, psbt: String) -> Result<String, JsValue> {
+        self.with_key(|k| key_sign_psbt(k, psbt))
+    }
+
+    #[allow(non_snake_case)]
+    pub fn toWif(&self, network: Network) -> String {
+        self.with_key(|k| k.to_wif(network))
+    }
+
+    #[allow(non_snake_case)]
+    pub fn watchOnly(&self) -> JsWatchOnlyWallet {
+        JsWatchOnlyWallet {
+            inner: self.with_child(|c| c.watch_only()),
         }
-        let txs = txs
-            .into_iter()
-            .map(|t| hex::encode(t))
-            .collect::<Vec<String>>();
+    }
 
-        Ok(js_try!(JsValue::from_serde(&txs)))
+    #[allow(non_snake_case)]
+    pub fn syncNonce(&mut self, observed: Vec<u8>) {
+        self.with_key(|k| k.sync_nonce(&observed))
+    }
+
+    #[allow(non_snake_case)]
+    pub fn signMessage(&self, message: String, network: Network) -> Result<String, JsValue> {
+        Ok(js_try!(self.with_key(|k| k.sign_message(&message, network))))
+    }
+}
+impl JsChildWallet {
+    fn with_child<T, F: FnOnce(&mut ChildWallet) -> T>(&self, f: F) -> T {
+        let mut wallet = self.wallet.borrow_mut();
+        f(wallet
+            .derive(&self.path)
+            .expect("child derived by JsWallet::child"))
+    }
+
+    fn with_key<T, F: FnOnce(&mut KeyWallet) -> T>(&self, f: F) -> T {
+        self.with_child(|c| f(c.key_mut()))
+    }
+}
+
+#[wasm_bindgen]
+pub struct JsKeyWallet {
+    inner: KeyWallet,
+}
+
+#[wasm_bindgen]
+impl JsKeyWallet {
+    #[allow(non_snake_case)]
+    pub fn fromWif(wif: String, network: Network) -> Result<JsKeyWallet, JsValue> {
+        Ok(JsKeyWallet {
+            inner: js_try!(KeyWallet::from_wif(&wif, network)),
+        })
+    }
+
+    #[allow(non_snake_case)]
+    pub fn toWif(&self, network: Network) -> String {
+        self.inner.to_wif(network)
+    }
+
+    pub fn address(&self, network: Network) -> String {
+        self.inner.address(network)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn syncNonce(&mut self, observed: Vec<u8>) {
+        self.inner.sync_nonce(&observed)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn signMessage(&self, message: String, network: Network) -> Result<String, JsValue> {
+        Ok(js_try!(self.inner.sign_message(&message, network)))
+    }
+
+    #[allow(non_snake_case)]
+    pub fn newBork(
+        &mut self,
+        data: JsValue,
+        inputs: JsValue,
+        recipient: JsValue,
+        mentions: JsValue,
+        fee: JsValue,
+        network: Network,
+        version: Option<u16>,
+        rbf: Option<bool>,
+    ) -> Result<JsValue, JsValue> {
+        key_new_bork(
+            &mut self.inner,
+            data,
+            inputs,
+            recipient,
+            mentions,
+            fee,
+            network,
+            version,
+            rbf,
+        )
+    }
+
+    #[allow(non_snake_case)]
+    pub fn planBork(
+        &self,
+        data: JsValue,
+        inputs: JsValue,
+        recipient: JsValue,
+        mentions: JsValue,
+        fee: JsValue,
+        network: Network,
+        version: Option<u16>,
+    ) -> Result<JsValue, JsValue> {
+        key_plan_bork(
+            &self.inner,
+            data,
+            inputs,
+            recipient,
+            mentions,
+            fee,
+            network,
+            version,
+        )
     }
 
     #[allow(non_snake_case)]
@@ -216,23 +1146,402 @@ impl JsChildWallet {
         &self,
         inputs: JsValue,
         destination: String,
-        amount: f64,
-        fee: f64,
+        amount: JsValue,
+        fee: JsValue,
+        network: Network,
+    ) -> Result<String, JsValue> {
+        key_construct_signed(&self.inner, inputs, destination, amount, fee, network)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn buildSigned(
+        &self,
+        inputs: JsValue,
+        outputs: JsValue,
+        op_return: Option<String>,
+        options: JsValue,
+        network: Network,
+    ) -> Result<JsValue, JsValue> {
+        key_build_signed(&self.inner, inputs, outputs, op_return, options, network)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn bumpFee(
+        &self,
+        chain: JsValue,
+        inputs: JsValue,
+        options: JsValue,
+        network: Network,
+    ) -> Result<JsValue, JsValue> {
+        key_bump_fee(&self.inner, chain, inputs, options, network)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn cpfp(
+        &self,
+        parent: String,
+        parent_fee: JsValue,
+        options: JsValue,
+        network: Network,
+    ) -> Result<JsValue, JsValue> {
+        key_cpfp(&self.inner, parent, parent_fee, options, network)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn createPsbt(
+        &self,
+        inputs: JsValue,
+        outputs: JsValue,
+        fee: JsValue,
+        op_return: Option<String>,
+        network: Network,
+    ) -> Result<String, JsValue> {
+        new_psbt(
+            inputs,
+            outputs,
+            fee,
+            op_return,
+            &self.inner.script(),
+            self.inner.script_sig_len(),
+            None,
+            network,
+        )
+    }
+
+    #[allow(non_snake_case)]
+    pub fn signPsbt(&self, psbt: String) -> Result<String, JsValue> {
+        key_sign_psbt(&self.inner, psbt)
+    }
+}
+
+#[wasm_bindgen]
+pub struct JsWatchOnlyWallet {
+    inner: WatchOnlyWallet,
+}
+
+#[wasm_bindgen]
+impl JsWatchOnlyWallet {
+    #[allow(non_snake_case)]
+    pub fn fromXpub(xpub: String) -> Result<JsWatchOnlyWallet, JsValue> {
+        Ok(JsWatchOnlyWallet {
+            inner: js_try!(WatchOnlyWallet::from_xpub(&xpub)),
+        })
+    }
+
+    #[allow(non_snake_case)]
+    pub fn fromPublicKey(pubkey: String, chain_code: String) -> Result<JsWatchOnlyWallet, JsValue> {
+        Ok(JsWatchOnlyWallet {
+            inner: js_try!(WatchOnlyWallet::from_public_key(
+                &js_try!(hex::decode(&pubkey)),
+                &js_try!(hex::decode(&chain_code)),
+            )),
+        })
+    }
+
+    #[allow(non_snake_case)]
+    pub fn publicKey(&self) -> String {
+        hex::encode(&self.inner.mpub().serialize_compressed()[..])
+    }
+
+    #[allow(non_snake_case)]
+    pub fn chainCode(&self) -> String {
+        hex::encode(self.inner.chain_code())
+    }
+
+    #[allow(non_snake_case)]
+    pub fn childAt(&mut self, derivation_path: Vec<f64>) -> Result<JsWatchOnlyWallet, JsValue> {
+        let path = js_try!(path_from_f64s(&derivation_path));
+        let child = js_try!(self.inner.derive(&path));
+        Ok(JsWatchOnlyWallet {
+            inner: js_try!(WatchOnlyWallet::from_parts(
+                child.mpub().clone(),
+                child.chain_code()
+            )),
+        })
+    }
+
+    #[allow(non_snake_case)]
+    pub fn childAtPath(&mut self, derivation_path: String) -> Result<JsWatchOnlyWallet, JsValue> {
+        let path: DerivationPath = js_try!(derivation_path.parse());
+        let child = js_try!(self.inner.derive(&path));
+        Ok(JsWatchOnlyWallet {
+            inner: js_try!(WatchOnlyWallet::from_parts(
+                child.mpub().clone(),
+                child.chain_code()
+            )),
+        })
+    }
+
+    pub fn address(&self, network: Network) -> String {
+        self.inner.address(network)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn constructUnsigned(
+        &self,
+        inputs: JsValue,
+        destination: String,
+        amount: JsValue,
+        fee: JsValue,
         network: Network,
     ) -> Result<String, JsValue> {
-        let inputs = js_try!(inputs.into_serde::<Vec<String>>());
-        let inputs = js_try!(inputs
+        let utxos = js_try!(parse_inputs(inputs, &[self.inner.script()]));
+        let options = TxOptions::new(js_try!(parse_fee(fee)));
+        let amount = js_try!(amount.into_serde::<Amount>());
+
+        let unsigned = js_try!(self.inner.build_unsigned(
+            &utxos,
+            &[(destination.as_str(), amount)],
+            None,
+            &options,
+            network
+        ));
+        Ok(hex::encode(js_try!(unsigned.serialize())))
+    }
+
+    /// like `constructUnsigned`, with several outputs and `TxOptions`, also
+    /// returning the outpoints spent
+    #[allow(non_snake_case)]
+    pub fn buildUnsigned(
+        &self,
+        inputs: JsValue,
+        outputs: JsValue,
+        op_return: Option<String>,
+        options: JsValue,
+        network: Network,
+    ) -> Result<JsValue, JsValue> {
+        build_tx(
+            inputs,
+            &[self.inner.script()],
+            outputs,
+            op_return,
+            options,
+            |utxos, outputs, op_return, options| {
+                self.inner
+                    .build_unsigned(utxos, outputs, op_return, options, network)
+            },
+        )
+    }
+
+    #[allow(non_snake_case)]
+    pub fn createPsbt(
+        &self,
+        inputs: JsValue,
+        outputs: JsValue,
+        fee: JsValue,
+        op_return: Option<String>,
+        network: Network,
+    ) -> Result<String, JsValue> {
+        new_psbt(
+            inputs,
+            outputs,
+            fee,
+            op_return,
+            &self.inner.script(),
+            self.inner.script_sig_len(),
+            None,
+            network,
+        )
+    }
+
+    #[allow(non_snake_case)]
+    pub fn toBuffer(&self) -> Result<String, JsValue> {
+        Ok(hex::encode(js_try!(self.inner.as_bytes())))
+    }
+
+    #[allow(non_snake_case)]
+    pub fn fromBuffer(buffer: String) -> Result<JsWatchOnlyWallet, JsValue> {
+        Ok(JsWatchOnlyWallet {
+            inner: js_try!(WatchOnlyWallet::from_bytes(&js_try!(hex::decode(&buffer)))),
+        })
+    }
+}
+
+#[wasm_bindgen]
+pub struct JsMultisigWallet {
+    inner: MultisigWallet,
+}
+
+#[wasm_bindgen]
+impl JsMultisigWallet {
+    /// `public_keys` is an array of hex encoded cosigner public keys
+    #[wasm_bindgen(constructor)]
+    pub fn new(threshold: u32, public_keys: JsValue) -> Result<JsMultisigWallet, JsValue> {
+        let public_keys = js_try!(public_keys.into_serde::<Vec<String>>());
+        let public_keys = js_try!(public_keys
             .into_iter()
-            .map(|i| hex::decode(i))
+            .map(|k| hex::decode(k))
             .collect::<Result<Vec<_>, _>>());
+        Ok(JsMultisigWallet {
+            inner: js_try!(MultisigWallet::from_public_keys(
+                threshold as usize,
+                &public_keys
+            )),
+        })
+    }
+
+    #[allow(non_snake_case)]
+    pub fn fromRedeemScript(redeem_script: String) -> Result<JsMultisigWallet, JsValue> {
+        Ok(JsMultisigWallet {
+            inner: js_try!(MultisigWallet::from_redeem_script(&js_try!(hex::decode(
+                &redeem_script
+            )))),
+        })
+    }
+
+    #[allow(non_snake_case)]
+    pub fn redeemScript(&self) -> String {
+        hex::encode(self.inner.redeem_script().as_bytes())
+    }
+
+    pub fn threshold(&self) -> u32 {
+        self.inner.threshold() as u32
+    }
+
+    pub fn address(&self, network: Network) -> String {
+        self.inner.address(network)
+    }
 
-        let signed = js_try!(self.inner.construct_signed(
-            &inputs,
-            &[(destination.as_str(), amount as u64)],
-            fee as u64,
+    /// partially signed by `signer`; extension transactions are refused since
+    /// they would spend a transaction whose id changes as cosigners sign
+    #[allow(non_snake_case)]
+    pub fn newBork(
+        &mut self,
+        signer: &JsChildWallet,
+        data: JsValue,
+        inputs: JsValue,
+        recipient: JsValue,
+        mentions: JsValue,
+        fee: JsValue,
+        network: Network,
+        version: Option<u16>,
+    ) -> Result<JsValue, JsValue> {
+        let options = TxOptions::new(js_try!(parse_fee(fee)));
+        let res = new_bork(
+            data,
+            inputs,
+            &[self.inner.script()],
+            recipient,
+            mentions,
+            self.inner.next_nonce(),
+            version,
+            options.max_op_return_len(network),
+            |utxos, outputs, op_rets| {
+                ensure!(
+                    op_rets.len() == 1,
+                    "multisig borks must fit in a single transaction"
+                );
+                chain_to_hex(vec![signer.with_child(|c| {
+                    self.inner
+                        .build_signed(c, utxos, outputs, Some(&op_rets[0]), &options, network)
+                })?])
+            },
+        )?;
+        // the nonce is used up only once the bork is signed
+        self.inner.nonce();
+        Ok(res)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn constructSigned(
+        &self,
+        signer: &JsChildWallet,
+        inputs: JsValue,
+        destination: String,
+        amount: JsValue,
+        fee: JsValue,
+        network: Network,
+    ) -> Result<String, JsValue> {
+        let utxos = js_try!(parse_inputs(inputs, &[self.inner.script()]));
+        let options = TxOptions::new(js_try!(parse_fee(fee)));
+        let amount = js_try!(amount.into_serde::<Amount>());
+
+        let signed = js_try!(signer.with_child(|c| self.inner.build_signed(
+            c,
+            &utxos,
+            &[(destination.as_str(), amount)],
             None,
+            &options,
             network
-        ));
-        Ok(hex::encode(signed))
+        )));
+        Ok(hex::encode(js_try!(signed.serialize())))
+    }
+
+    /// like `constructSigned`, with several outputs and `TxOptions`, also
+    /// returning the outpoints spent
+    #[allow(non_snake_case)]
+    pub fn buildSigned(
+        &self,
+        signer: &JsChildWallet,
+        inputs: JsValue,
+        outputs: JsValue,
+        op_return: Option<String>,
+        options: JsValue,
+        network: Network,
+    ) -> Result<JsValue, JsValue> {
+        build_tx(
+            inputs,
+            &[self.inner.script()],
+            outputs,
+            op_return,
+            options,
+            |utxos, outputs, op_return, options| {
+                signer.with_child(|c| {
+                    self.inner
+                        .build_signed(c, utxos, outputs, op_return, options, network)
+                })
+            },
+        )
+    }
+
+    #[allow(non_snake_case)]
+    pub fn createPsbt(
+        &self,
+        inputs: JsValue,
+        outputs: JsValue,
+        fee: JsValue,
+        op_return: Option<String>,
+        network: Network,
+    ) -> Result<String, JsValue> {
+        new_psbt(
+            inputs,
+            outputs,
+            fee,
+            op_return,
+            &self.inner.script(),
+            self.inner.script_sig_len(),
+            Some(self.inner.redeem_script()),
+            network,
+        )
+    }
+
+    /// adds `signer`'s signature to a partially signed transaction
+    pub fn sign(&self, signer: &JsChildWallet, tx: String) -> Result<String, JsValue> {
+        let tx = js_try!(hex::decode(&tx));
+        Ok(hex::encode(js_try!(
+            signer.with_child(|c| self.inner.sign(c, &tx))
+        )))
+    }
+
+    #[allow(non_snake_case)]
+    pub fn isComplete(&self, tx: String) -> Result<bool, JsValue> {
+        Ok(js_try!(self.inner.is_complete(&js_try!(hex::decode(&tx)))))
+    }
+
+    #[allow(non_snake_case)]
+    pub fn syncNonce(&mut self, observed: Vec<u8>) {
+        self.inner.sync_nonce(&observed)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn toBuffer(&self) -> Result<String, JsValue> {
+        Ok(hex::encode(js_try!(self.inner.as_bytes())))
+    }
+
+    #[allow(non_snake_case)]
+    pub fn fromBuffer(buffer: String) -> Result<JsMultisigWallet, JsValue> {
+        Ok(JsMultisigWallet {
+            inner: js_try!(MultisigWallet::from_bytes(&js_try!(hex::decode(&buffer)))),
+        })
     }
 }
@@ -15,10 +15,7 @@ macro_rules! mask_16 {
 #[macro_export]
 macro_rules! js_try {
     ($x:expr) => {
-        ($x).map_err(|e| {
-            let e = failure::Error::from(e);
-            format_js_err!("{}: {}\n{}", line!(), e, e.backtrace())
-        })?
+        ($x).map_err(|e| $crate::js_error(line!(), failure::Error::from(e)))?
     };
 }
 
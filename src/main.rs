@@ -18,6 +18,22 @@ fn count_match(a: &str, b: &str, case_sensitive: bool) -> usize {
     count
 }
 
+/// the outputs of the hex transactions `funding` paying to any of `keys`
+fn sweep_utxos(
+    keys: &[lib::KeyWallet],
+    funding: &[String],
+) -> Result<Vec<lib::Utxo>, failure::Error> {
+    let funding = funding
+        .iter()
+        .map(hex::decode)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(keys
+        .iter()
+        .map(|k| lib::Utxo::from_txs(&funding, &k.script()))
+        .collect::<Result<Vec<_>, _>>()?
+        .concat())
+}
+
 fn main() -> Result<(), failure::Error> {
     let cmd: Vec<String> = args().collect();
     let name = cmd.get(0).unwrap();
@@ -48,6 +64,150 @@ fn main() -> Result<(), failure::Error> {
             }
             None => eprintln!("usage: {} wallet_from_bytes <hexdata>", name),
         },
+        Some("encrypt_wallet") => match (cmd.get(2), cmd.get(3)) {
+            (Some(a), Some(password)) => {
+                let ent = lib::Wallet::from_bytes(&hex::decode(a)?)?;
+                println!("{}", hex::encode(&ent.as_encrypted_bytes(password)?));
+            }
+            _ => eprintln!("usage: {} encrypt_wallet <hexdata> <password>", name),
+        },
+        Some("wallet_from_encrypted") => match (cmd.get(2), cmd.get(3)) {
+            (Some(a), Some(password)) => {
+                let ent = lib::Wallet::from_encrypted_bytes(&hex::decode(a)?, password)?;
+                println!("{}", ent.words().join(" "));
+            }
+            _ => eprintln!("usage: {} wallet_from_encrypted <hexdata> <password>", name),
+        },
+        Some("split_wallet") => match (cmd.get(2), cmd.get(3), cmd.get(4..16)) {
+            (Some(threshold), Some(groups), Some(words)) => {
+                let ent = lib::Wallet::from_words(words)?;
+                let groups = groups
+                    .split(',')
+                    .map(|g| {
+                        let mut it = g.splitn(2, '/').map(|n| n.parse::<u8>());
+                        match (it.next(), it.next()) {
+                            (Some(Ok(t)), Some(Ok(n))) => Ok((t, n)),
+                            _ => Err(failure::format_err!("invalid group {}", g)),
+                        }
+                    })
+                    .collect::<Result<Vec<_>, failure::Error>>()?;
+                for (i, group) in ent.split(threshold.parse()?, &groups)?.iter().enumerate() {
+                    for (j, share) in group.iter().enumerate() {
+                        println!("group {} share {}: {}", i + 1, j + 1, share.words().join(" "));
+                    }
+                }
+            }
+            _ => eprintln!(
+                "usage: {} split_wallet <group_threshold> <threshold/count,...> <word1> ... <word12>",
+                name
+            ),
+        },
+        Some("combine_wallet") => match cmd.get(2..) {
+            Some(a) if !a.is_empty() => {
+                let shares = a
+                    .iter()
+                    .map(|s| {
+                        let words: Vec<String> = s.split_whitespace().map(String::from).collect();
+                        lib::Share::from_words(&words)
+                    })
+                    .collect::<Result<Vec<_>, failure::Error>>()?;
+                let ent = lib::Wallet::combine(&shares)?;
+                println!("{}", ent.words().join(" "));
+            }
+            _ => eprintln!("usage: {} combine_wallet \"<share words>\" ...", name),
+        },
+        Some("sign_message") => match (cmd.get(2), cmd.get(3), cmd.get(4)) {
+            (Some(a), Some(path), Some(message)) => {
+                let mut ent = lib::Wallet::from_bytes(&hex::decode(a)?)?;
+                let child = ent.derive(&path.parse()?)?;
+                println!("{}", child.address(lib::Network::Dogecoin));
+                println!("{}", child.key().sign_message(message, lib::Network::Dogecoin)?);
+            }
+            _ => eprintln!(
+                "usage: {} sign_message <hexdata> <derivation_path> <message>",
+                name
+            ),
+        },
+        Some("verify_message") => match (cmd.get(2), cmd.get(3), cmd.get(4)) {
+            (Some(address), Some(signature), Some(message)) => {
+                let valid =
+                    lib::verify_message(address, signature, message, lib::Network::Dogecoin)?;
+                println!("{}", valid);
+            }
+            _ => eprintln!(
+                "usage: {} verify_message <address> <signature> <message>",
+                name
+            ),
+        },
+        Some("sweep") => match (cmd.get(2), cmd.get(3), cmd.get(4), cmd.get(5..)) {
+            (Some(keys), Some(destination), Some(fee), Some(funding)) if !funding.is_empty() => {
+                let network = lib::Network::Dogecoin;
+                let keys = keys
+                    .split(',')
+                    .map(|wif| lib::KeyWallet::from_wif(wif, network))
+                    .collect::<Result<Vec<_>, failure::Error>>()?;
+                let built = lib::sweep(
+                    &keys,
+                    &sweep_utxos(&keys, funding)?,
+                    destination,
+                    &lib::TxOptions::new(lib::Fee::PerKb(fee.parse()?)),
+                    network,
+                )?;
+                println!("{}", hex::encode(built.serialize()?));
+            }
+            _ => eprintln!(
+                "usage: {} sweep <wif,...> <destination> <fee_per_kb> <funding_hextx> ...",
+                name
+            ),
+        },
+        Some("sweep_wallet") => match (
+            cmd.get(2),
+            cmd.get(3),
+            cmd.get(4),
+            cmd.get(5),
+            cmd.get(6..),
+        ) {
+            (Some(a), Some(paths), Some(destination), Some(fee), Some(funding))
+                if !funding.is_empty() =>
+            {
+                let network = lib::Network::Dogecoin;
+                let mut ent = lib::Wallet::from_bytes(&hex::decode(a)?)?;
+                let paths = paths
+                    .split(',')
+                    .map(|p| p.parse())
+                    .collect::<Result<Vec<lib::DerivationPath>, failure::Error>>()?;
+                let keys = paths
+                    .iter()
+                    .map(|p| Ok(ent.derive(p)?.to_key_wallet()))
+                    .collect::<Result<Vec<_>, failure::Error>>()?;
+                let built = ent.sweep(
+                    &paths,
+                    &sweep_utxos(&keys, funding)?,
+                    destination,
+                    &lib::TxOptions::new(lib::Fee::PerKb(fee.parse()?)),
+                    network,
+                )?;
+                println!("{}", hex::encode(built.serialize()?));
+            }
+            _ => eprintln!(
+                "usage: {} sweep_wallet <hexdata> <derivation_path,...> <destination> <fee_per_kb> <funding_hextx> ...",
+                name
+            ),
+        },
+        Some("inspect_tx") => match cmd.get(2) {
+            Some(tx) => {
+                let prevouts = cmd[3..]
+                    .iter()
+                    .map(|f| lib::Utxo::outputs_of(&hex::decode(f)?))
+                    .collect::<Result<Vec<_>, failure::Error>>()?
+                    .concat();
+                let time = chrono::Utc::now();
+                let info =
+                    lib::inspect_tx(&hex::decode(tx)?, &prevouts, &time, lib::Network::Dogecoin)?;
+                println!("{}", serde_json::to_string_pretty(&info)?);
+            }
+            None => eprintln!("usage: {} inspect_tx <hextx> [funding_hextx...]", name),
+        },
         Some("parse_block") => {
             let mut v: serde_json::Value =
                 serde_json::from_reader(std::fs::File::open("./blockdata.json").unwrap()).unwrap();
@@ -59,19 +219,17 @@ fn main() -> Result<(), failure::Error> {
             Some(target) => {
                 let sensitive = cmd.get(1).unwrap() == "vanity";
                 let mut best: Option<(usize, lib::Wallet)> = None;
+                let path: lib::DerivationPath = match cmd.get(3) {
+                    Some(p) => p.parse()?,
+                    None => "m/44'/3'/0'/0/0".parse()?,
+                };
                 bitcoin::util::base58::from(target)?;
                 if target.chars().next().unwrap() != 'D' {
                     failure::bail!("doge address must start with 'D'")
                 };
                 loop {
                     let mut wallet = lib::Wallet::new();
-                    let child = wallet
-                        .parent_mut()
-                        .load_child(44, true)?
-                        .load_child(3, true)?
-                        .load_child(0, true)?
-                        .load_child(0, false)?
-                        .load_child(0, false)?;
+                    let child = wallet.derive(&path)?;
                     let addr = child.address(lib::Network::Dogecoin);
                     let count = count_match(target, &addr, sensitive);
                     match best {
@@ -97,7 +255,7 @@ fn main() -> Result<(), failure::Error> {
                     None => (),
                 }
             }
-            None => eprintln!("usage: {} vanity <address_prefix>", name),
+            None => eprintln!("usage: {} vanity <address_prefix> [derivation_path]", name),
         },
         None | Some("help") => eprintln!("usage: {} <command> [args...]", name),
         Some(a) => eprintln!("'{}' is not a valid command", a),
@@ -54,6 +54,11 @@ pub struct BorkTxData<'a> {
     mentions: Vec<String>,
     tags: Vec<String>,
 }
+impl<'a> BorkTxData<'a> {
+    pub fn sender_address(&self) -> &str {
+        &self.sender_address
+    }
+}
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -211,7 +216,14 @@ impl std::convert::TryFrom<NewBorkData> for NewBork {
     }
 }
 
-pub fn encode(bork: NewBork, nonce: u8, version: Option<u16>) -> Result<Vec<Vec<u8>>, Error> {
+/// the payloads of OP_RETURNs carrying `bork`, each at most `max_len` bytes,
+/// continued in extensions when the content does not fit in one
+pub fn encode(
+    bork: NewBork,
+    nonce: u8,
+    version: Option<u16>,
+    max_len: usize,
+) -> Result<Vec<Vec<u8>>, Error> {
     let mut buf_vec: Vec<Vec<u8>> = Vec::new();
     let mut buf: Vec<u8> = Vec::new();
     let magic = version.map(u16::to_be_bytes).unwrap_or(MAGIC);
@@ -285,10 +297,20 @@ pub fn encode(bork: NewBork, nonce: u8, version: Option<u16>) -> Result<Vec<Vec<
             None
         }
     };
+    ensure!(
+        buf.len() <= max_len,
+        "bork of {} bytes is over the maximum of {}",
+        buf.len(),
+        max_len
+    );
     if let Some(content) = content {
-        let remaining = (80 - buf.len()).min(content.len());
+        // magic, type, nonce and index
+        let chunk_len = max_len.saturating_sub(5);
+        ensure!(chunk_len > 0, "no room for content in {} bytes", max_len);
+        let remaining = (max_len - buf.len()).min(content.len());
         buf.write(&content[..remaining])?;
-        for c in content[remaining..].chunks(75) {
+        for c in content[remaining..].chunks(chunk_len) {
+            ensure!(buf_vec.len() < 255, "bork needs more than 255 extensions");
             buf_vec.push(buf);
             buf = Vec::new();
             buf.extend_from_slice(&magic);
@@ -587,6 +609,88 @@ pub fn decode<'a>(
     Ok(res)
 }
 
+/// address of a p2pkh input whose signature verifies
+pub fn p2pkh_signer(tx: &bitcoin::Transaction, input: usize, network: Network) -> Option<String> {
+    p2pkh_signature(tx, input, network).map(|(addr, _)| addr)
+}
+
+/// the address of a p2pkh input whose signature verifies, and whether its key
+/// is compressed
+fn p2pkh_signature(
+    tx: &bitcoin::Transaction,
+    input: usize,
+    network: Network,
+) -> Option<(String, bool)> {
+    tx.input.get(input).and_then(|i| {
+        use bitcoin_hashes::Hash;
+
+        let mut script = i.script_sig.iter(true);
+        let mut sig = match script.next() {
+            Some(bitcoin::blockdata::script::Instruction::PushBytes(b)) => b.to_vec(),
+            _ => return None,
+        };
+        let pubkey = match script.next() {
+            Some(bitcoin::blockdata::script::Instruction::PushBytes(b)) => b,
+            _ => return None,
+        };
+        if sig.len() == 0 {
+            return None;
+        }
+        let sighash_type = sig.remove(sig.len() - 1);
+        let addr = pubkey_to_addr(pubkey, network);
+        let msg = secp256k1::Message::parse_slice(
+            &tx.signature_hash(
+                input,
+                &addr_to_script(&addr, network).ok()?,
+                sighash_type as u32,
+            )
+            .into_inner(),
+        )
+        .ok()?;
+        if !secp256k1::verify(
+            &msg,
+            &secp256k1::Signature::parse_der_lax(&sig).ok()?,
+            &secp256k1::PublicKey::parse_slice(pubkey, None).ok()?,
+        ) {
+            return None;
+        }
+
+        Some((addr, pubkey.len() == 33))
+    })
+}
+
+/// script address of a p2sh multisig input carrying at least `threshold`
+/// valid signatures
+pub fn p2sh_multisig_signer(
+    tx: &bitcoin::Transaction,
+    input: usize,
+    network: Network,
+) -> Option<String> {
+    use crate::wallet::MultisigWallet;
+
+    let redeem_script = match tx.input.get(input)?.script_sig.iter(true).last() {
+        Some(bitcoin::blockdata::script::Instruction::PushBytes(b)) => b,
+        _ => return None,
+    };
+    let wallet = MultisigWallet::from_redeem_script(redeem_script).ok()?;
+    if wallet.input_signature_count(tx, input)? < wallet.threshold() {
+        return None;
+    }
+
+    Some(wallet.address(network))
+}
+
+/// the data pushed by an OP_RETURN output
+pub fn op_return_data(script: &bitcoin::Script) -> Option<&[u8]> {
+    let b = script.as_bytes();
+    match b.get(1) {
+        Some(0x4c) => b.get(3..),
+        Some(0x4d) => b.get(4..),
+        Some(0x4e) => b.get(6..),
+        _ => b.get(2..),
+    }
+}
+
 pub fn parse_tx<'a>(
     tx: bitcoin::Transaction,
     time: &'a DateTime<Utc>,
@@ -596,53 +700,17 @@ pub fn parse_tx<'a>(
 
     let mut tx_data: Vec<u8> = Vec::new();
     tx.consensus_encode(&mut tx_data).unwrap();
-    let from = {
-        tx.input.get(0).and_then(|i| {
-            use bitcoin_hashes::Hash;
-
-            let mut script = i.script_sig.iter(true);
-            let mut sig = match script.next() {
-                Some(bitcoin::blockdata::script::Instruction::PushBytes(b)) => b.to_vec(),
-                _ => return None,
-            };
-            let pubkey = match script.next() {
-                Some(bitcoin::blockdata::script::Instruction::PushBytes(b)) => b,
-                _ => return None,
-            };
-            if sig.len() == 0 {
-                return None;
-            }
-            let sighash_type = sig.remove(sig.len() - 1);
-            let addr = pubkey_to_addr(pubkey, network);
-            let msg = secp256k1::Message::parse_slice(
-                &tx.signature_hash(
-                    0,
-                    &addr_to_script(&addr, network).ok()?,
-                    sighash_type as u32,
-                )
-                .into_inner(),
-            )
-            .ok()?;
-            if !secp256k1::verify(
-                &msg,
-                &secp256k1::Signature::parse_der_lax(&sig).ok()?,
-                &secp256k1::PublicKey::parse_slice(
-                    pubkey,
-                    Some(secp256k1::PublicKeyFormat::Compressed),
-                )
-                .ok()?,
-            ) {
-                return None;
-            }
-
-            Some(addr)
-        })
-    };
+    // borks have only ever been from compressed keys, and indexers on every
+    // version must agree on who sent them
+    let from = p2pkh_signature(&tx, 0, network)
+        .filter(|(_, compressed)| *compressed)
+        .map(|(addr, _)| addr)
+        .or_else(|| p2sh_multisig_signer(&tx, 0, network));
     let txid = format!("{:x}", tx.txid());
     let mut op_ret = None;
     let mut created = Vec::new();
     for (idx, o) in tx.output.iter().enumerate() {
-        if o.script_pubkey.is_p2pkh() {
+        if o.script_pubkey.is_p2pkh() || o.script_pubkey.is_p2sh() {
             created.push(NewUtxo {
                 txid: txid.clone(),
                 position: idx as u32,
@@ -650,13 +718,7 @@ pub fn parse_tx<'a>(
                 value: o.value,
             });
         } else if o.script_pubkey.is_op_return() {
-            let b = o.script_pubkey.as_bytes();
-            match b.get(1) {
-                Some(0x4c) => op_ret = b.get(3..),
-                Some(0x4d) => op_ret = b.get(4..),
-                Some(0x4e) => op_ret = b.get(6..),
-                _ => op_ret = b.get(2..),
-            };
+            op_ret = op_return_data(&o.script_pubkey);
         }
     }
 
@@ -0,0 +1,169 @@
+//! Values in the smallest unit of a coin (koinu, litoshis, satoshis), parsed
+//! from and formatted as decimal coin amounts without going through floats.
+
+use crate::Network;
+use bigdecimal::{BigDecimal, Signed, ToPrimitive};
+use failure::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// units in a coin, the same on every network
+pub const COIN: u64 = 100_000_000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(u64);
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+
+    pub fn from_sat(sat: u64) -> Self {
+        Amount(sat)
+    }
+
+    pub fn as_sat(self) -> u64 {
+        self.0
+    }
+
+    /// a decimal number of coins, e.g. "12.5"
+    pub fn from_coins(coins: &str) -> Result<Self, Error> {
+        let sat = BigDecimal::from_str(coins)
+            .map_err(|e| format_err!("invalid amount {}: {}", coins, e))?
+            * BigDecimal::from(COIN);
+        ensure!(!sat.is_negative(), "negative amount {}", coins);
+        ensure!(sat.is_integer(), "{} has more than 8 decimal places", coins);
+        sat.to_u64()
+            .map(Amount)
+            .ok_or(format_err!("{} is out of range", coins))
+    }
+
+    /// a decimal number of coins in the unit of `network`, e.g. "12.5 DOGE";
+    /// the unit may be left out
+    pub fn parse(s: &str, network: Network) -> Result<Self, Error> {
+        let (coins, unit) = split_unit(s)?;
+        if let Some(unit) = unit {
+            ensure!(
+                unit.eq_ignore_ascii_case(network.ticker()),
+                "{} is not an amount of {}",
+                s,
+                network.ticker()
+            );
+        }
+        Self::from_coins(coins)
+    }
+
+    /// e.g. "12.5 DOGE"
+    pub fn format(self, network: Network) -> String {
+        format!("{} {}", self, network.ticker())
+    }
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+
+    pub fn checked_mul(self, n: u64) -> Option<Amount> {
+        self.0.checked_mul(n).map(Amount)
+    }
+
+    /// the total of `amounts`, or `None` on overflow
+    pub fn checked_sum<I: IntoIterator<Item = Amount>>(amounts: I) -> Option<Amount> {
+        amounts
+            .into_iter()
+            .try_fold(Amount::ZERO, |acc, a| acc.checked_add(a))
+    }
+}
+impl From<u64> for Amount {
+    fn from(sat: u64) -> Self {
+        Amount(sat)
+    }
+}
+/// in coins, with no more decimal places than needed
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let frac = format!("{:08}", self.0 % COIN);
+        let frac = frac.trim_end_matches('0');
+        if frac.is_empty() {
+            write!(f, "{}", self.0 / COIN)
+        } else {
+            write!(f, "{}.{}", self.0 / COIN, frac)
+        }
+    }
+}
+/// like `parse`, accepting the unit of any network
+impl FromStr for Amount {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let (coins, unit) = split_unit(s)?;
+        if let Some(unit) = unit {
+            ensure!(
+                [Network::Dogecoin, Network::Litecoin, Network::Bitcoin]
+                    .iter()
+                    .any(|n| unit.eq_ignore_ascii_case(n.ticker())),
+                "unknown unit {}",
+                unit
+            );
+        }
+        Self::from_coins(coins)
+    }
+}
+
+fn split_unit(s: &str) -> Result<(&str, Option<&str>), Error> {
+    let mut parts = s.split_whitespace();
+    let coins = parts.next().ok_or(format_err!("empty amount"))?;
+    let unit = parts.next();
+    ensure!(parts.next().is_none(), "invalid amount {}", s);
+    Ok((coins, unit))
+}
+
+impl serde::Serialize for Amount {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.0)
+    }
+}
+/// a whole number of the smallest unit, or a string of coins as `FromStr`
+/// takes, which stays exact beyond the 2^53 a JavaScript number holds
+impl<'de> serde::Deserialize<'de> for Amount {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Amount;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(
+                    f,
+                    "a whole number of the smallest unit or a string of coins"
+                )
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Amount, E> {
+                Ok(Amount(v))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Amount, E> {
+                if v < 0 {
+                    return Err(E::custom(format!("negative amount {}", v)));
+                }
+                Ok(Amount(v as u64))
+            }
+
+            fn visit_f64<E: serde::de::Error>(self, v: f64) -> Result<Amount, E> {
+                // 2^64 itself is representable, but too big
+                if v.fract() != 0.0 || !(0.0..18_446_744_073_709_551_616.0).contains(&v) {
+                    return Err(E::custom(format!(
+                        "{} is not a whole number of the smallest unit",
+                        v
+                    )));
+                }
+                Ok(Amount(v as u64))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Amount, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+        deserializer.deserialize_any(Visitor)
+    }
+}
@@ -0,0 +1,225 @@
+//! Speeding up a stuck bork: replacing it and its extensions with transactions
+//! paying more, as BIP125 allows once it signalled RBF, or spending its change
+//! in a child paying for both.
+
+use super::amount::Amount;
+use super::chain;
+use super::error::TxBuildError;
+use super::fee::{self, Fee};
+use super::script_to_addr;
+use super::select::CoinSelection;
+use super::tx::{BuiltTx, Sender, TxOptions, Utxo, RBF_SEQUENCE};
+use crate::Network;
+use bitcoin::blockdata::script::Instruction;
+use bitcoin::{OutPoint, Script, Transaction};
+use failure::Error;
+use std::collections::HashMap;
+
+/// the addresses a transaction pays and how much
+type Recipients = Vec<(String, Amount)>;
+
+/// the recipients and OP_RETURN payload of `tx`, leaving out change, taken to
+/// be the last output paying to `change_script`
+fn decompose(
+    tx: &Transaction,
+    change_script: &Script,
+    network: Network,
+) -> Result<(Recipients, Option<Vec<u8>>), Error> {
+    let mut outputs = tx.output.iter().collect::<Vec<_>>();
+    let payload = match outputs.last() {
+        Some(o) if o.script_pubkey.is_op_return() => Some(
+            o.script_pubkey
+                .iter(false)
+                .filter_map(|i| match i {
+                    Instruction::PushBytes(b) => Some(b.to_vec()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .concat(),
+        ),
+        _ => None,
+    };
+    if payload.is_some() {
+        outputs.pop();
+    }
+    if outputs.last().map(|o| &o.script_pubkey) == Some(change_script) {
+        outputs.pop();
+    }
+    let recipients = outputs
+        .into_iter()
+        .map(|o| {
+            Ok((
+                script_to_addr(&o.script_pubkey, network)?,
+                Amount::from_sat(o.value),
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok((recipients, payload))
+}
+
+/// Rebuilds `chain`, a transaction and any extensions spending its change,
+/// with the same inputs, recipients and payloads at the fee in `options`.
+/// `utxos` are the outputs the first transaction spends. Its replacement must
+/// pay more than everything it evicts plus relay for itself, so its fee is
+/// raised to that if needed.
+pub fn replace<F>(
+    chain: &[Vec<u8>],
+    utxos: &[Utxo],
+    change_script: &Script,
+    options: &TxOptions,
+    network: Network,
+    mut build: F,
+) -> Result<Vec<BuiltTx>, Error>
+where
+    F: FnMut(&[Utxo], &[(&str, Amount)], Option<&[u8]>, &TxOptions) -> Result<BuiltTx, Error>,
+{
+    let txs = chain
+        .iter()
+        .map(|tx| bitcoin::consensus::deserialize(tx))
+        .collect::<Result<Vec<Transaction>, _>>()?;
+    let head = txs
+        .first()
+        .ok_or(format_err!("no transactions to replace"))?;
+    chain::check_chained_change(txs.len(), options)?;
+    ensure!(
+        head.input.iter().any(|i| i.sequence <= RBF_SEQUENCE),
+        "transaction {} does not signal replaceability",
+        head.txid()
+    );
+
+    let mut values = utxos
+        .iter()
+        .map(|u| (u.outpoint, u.output.value))
+        .collect::<HashMap<_, _>>();
+    let mut replaced_fee = 0u64;
+    for tx in &txs {
+        let input = tx
+            .input
+            .iter()
+            .try_fold(0u64, |acc, i| {
+                values
+                    .get(&i.previous_output)
+                    .and_then(|v| acc.checked_add(*v))
+            })
+            .ok_or(format_err!(
+                "transaction {} spends an unknown output",
+                tx.txid()
+            ))?;
+        let output = tx
+            .output
+            .iter()
+            .try_fold(0u64, |acc, o| acc.checked_add(o.value))
+            .ok_or(format_err!("output value overflow"))?;
+        replaced_fee = input
+            .checked_sub(output)
+            .and_then(|fee| replaced_fee.checked_add(fee))
+            .ok_or(format_err!(
+                "transaction {} spends more than its inputs",
+                tx.txid()
+            ))?;
+        values.extend(tx.output.iter().enumerate().map(|(vout, o)| {
+            (
+                OutPoint {
+                    txid: tx.txid(),
+                    vout: vout as u32,
+                },
+                o.value,
+            )
+        }));
+    }
+
+    // spend exactly what was spent before, so the replacement conflicts
+    let head_utxos = head
+        .input
+        .iter()
+        .filter_map(|i| utxos.iter().find(|u| u.outpoint == i.previous_output))
+        .cloned()
+        .collect::<Vec<_>>();
+    let options = TxOptions {
+        coin_selection: CoinSelection::All,
+        rbf: true,
+        ..options.clone()
+    };
+    let (recipients, payload) = decompose(head, change_script, network)?;
+    let recipients = recipients
+        .iter()
+        .map(|(addr, value)| (addr.as_str(), *value))
+        .collect::<Vec<_>>();
+    let mut built = build(&head_utxos, &recipients, payload.as_deref(), &options)?;
+    let required = replaced_fee
+        .checked_add(fee::min_relay_fee(built.serialize()?.len() as u64, network))
+        .ok_or(format_err!("fee overflow"))?;
+    if built.fee.as_sat() < required {
+        built = build(
+            &head_utxos,
+            &recipients,
+            payload.as_deref(),
+            &TxOptions {
+                fee: Fee::Flat(required),
+                ..options.clone()
+            },
+        )?;
+    }
+
+    let mut res = vec![built];
+    for tx in &txs[1..] {
+        let (recipients, payload) = decompose(tx, change_script, network)?;
+        ensure!(
+            recipients.is_empty() && tx.input.len() == 1,
+            "transaction {} is not an extension",
+            tx.txid()
+        );
+        let change = res
+            .last()
+            .and_then(|prev| prev.change_utxo())
+            .ok_or(format_err!(
+                "replacement leaves no change to fund transaction {}",
+                tx.txid()
+            ))?;
+        res.push(build(&[change], &[], payload.as_deref(), &options)?);
+    }
+    Ok(res)
+}
+
+/// Spends every output of `parent` paying to `sender`'s first script back to
+/// change, paying enough that the two together meet the fee rate in `options`.
+/// `parent_fee` is what the parent pays already.
+pub fn child_pays_for_parent<F>(
+    parent: &[u8],
+    parent_fee: Amount,
+    sender: &Sender,
+    options: &TxOptions,
+    network: Network,
+    build: F,
+) -> Result<BuiltTx, Error>
+where
+    F: FnOnce(&[Utxo], &TxOptions) -> Result<BuiltTx, Error>,
+{
+    let utxos = Utxo::from_tx(parent, &sender.scripts[0])?;
+    if utxos.is_empty() {
+        return Err(TxBuildError::NoMatchingInputs {
+            reason: "the parent pays nothing to this wallet".to_owned(),
+        }
+        .into());
+    }
+    let change_script = options.change_script(sender.address, network)?;
+    let size = fee::estimate_size(utxos.len(), sender.script_sig_len, &[change_script.len()]);
+    let fee = options
+        .fee
+        .amount(parent.len() + size, network)
+        .saturating_sub(parent_fee.as_sat())
+        .max(fee::min_relay_fee(size as u64, network));
+    let built = build(
+        &utxos,
+        &TxOptions {
+            fee: Fee::Flat(fee),
+            coin_selection: CoinSelection::All,
+            ..options.clone()
+        },
+    )?;
+    ensure!(
+        built.change > Amount::ZERO,
+        "the parent's change is too small to pay for it"
+    );
+    Ok(built)
+}
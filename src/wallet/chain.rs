@@ -0,0 +1,84 @@
+//! Borks too long for one OP_RETURN are split over a chain of transactions,
+//! each extension spending the change of the one before. The chain is worked
+//! out unsigned first, so a shortfall is found before anything is signed.
+
+use super::amount::Amount;
+use super::error::TxBuildError;
+use super::tx::{BuiltTx, ChangePolicy, TxOptions, Utxo};
+use failure::Error;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedTx {
+    pub fee: Amount,
+    pub change: Amount,
+    pub payload_len: usize,
+}
+
+/// what a bork will cost, for showing before it is signed
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BorkPlan {
+    pub txs: Vec<PlannedTx>,
+    pub fee: Amount,
+    /// left over after the last transaction
+    pub change: Amount,
+}
+impl BorkPlan {
+    pub fn new(chain: &[BuiltTx], op_returns: &[Vec<u8>]) -> Self {
+        BorkPlan {
+            txs: chain
+                .iter()
+                .zip(op_returns)
+                .map(|(tx, op_return)| PlannedTx {
+                    fee: tx.fee,
+                    change: tx.change,
+                    payload_len: op_return.len(),
+                })
+                .collect(),
+            // the chain was built, so its fees add up
+            fee: Amount::checked_sum(chain.iter().map(|tx| tx.fee)).unwrap_or_default(),
+            change: chain.last().map(|tx| tx.change).unwrap_or_default(),
+        }
+    }
+}
+
+/// builds one transaction per OP_RETURN payload: the first spends `utxos` and
+/// pays `outputs`, each extension spends the change of the one before
+pub fn build_chain<F>(
+    utxos: &[Utxo],
+    outputs: &[(&str, Amount)],
+    op_returns: &[Vec<u8>],
+    options: &TxOptions,
+    mut build: F,
+) -> Result<Vec<BuiltTx>, Error>
+where
+    F: FnMut(&[Utxo], &[(&str, Amount)], &[u8]) -> Result<BuiltTx, Error>,
+{
+    check_chained_change(op_returns.len(), options)?;
+    let mut chain: Vec<BuiltTx> = Vec::with_capacity(op_returns.len());
+    for (i, op_return) in op_returns.iter().enumerate() {
+        let built = match chain.last() {
+            None => build(utxos, outputs, op_return)?,
+            Some(prev) => {
+                let change = prev.change_utxo().ok_or(format_err!(
+                    "transaction {} of {} leaves no change to fund the next",
+                    i,
+                    op_returns.len()
+                ))?;
+                build(&[change], &[], op_return)?
+            }
+        };
+        chain.push(built);
+    }
+    Ok(chain)
+}
+
+/// change paid elsewhere could not fund the next transaction of a chain of
+/// `txs`
+pub fn check_chained_change(txs: usize, options: &TxOptions) -> Result<(), Error> {
+    if txs > 1 && options.change != ChangePolicy::Sender {
+        return Err(TxBuildError::ChainedChange { txs }.into());
+    }
+    Ok(())
+}
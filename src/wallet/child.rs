@@ -1,45 +1,40 @@
-use super::addr_to_script;
-use super::pubkey_hash_to_addr;
+use super::is_legacy;
+use super::nonce;
+use super::ChildNumber;
+use super::DerivationPath;
 use super::HmacSha512;
+use super::KeyWallet;
+use super::WatchOnlyWallet;
+use super::{Secret, Seed};
 use crate::big_array::BigArray;
 use crate::Network;
 use failure::Error;
 use hmac::Mac;
-use ripemd160::Digest;
-use ripemd160::Ripemd160;
 use secp256k1::curve::Scalar;
 use secp256k1::{PublicKey, SecretKey};
-use sha2::Sha256;
-use std::collections::HashSet;
+use std::collections::BTreeMap;
+use zeroize::Zeroize;
 
 #[derive(Clone)]
 pub struct ChildWallet {
-    seed: [u8; 64],
-    mpriv: Option<SecretKey>,
-    mpub: Option<PublicKey>,
-    children: Vec<Option<ChildWallet>>,
-    hardened_children: Vec<Option<ChildWallet>>,
-    nonce: u8,
+    seed: Seed,
+    /// does all the signing for this node, with its key compressed as BIP32
+    /// keys always are
+    key: KeyWallet,
+    children: BTreeMap<ChildNumber, ChildWallet>,
 }
 impl ChildWallet {
     pub fn new(seed: [u8; 64]) -> Self {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-        let mut res = ChildWallet {
-            seed,
-            mpriv: None,
-            mpub: None,
-            children: Vec::new(),
-            hardened_children: Vec::new(),
-            nonce: rng.gen(),
-        };
-        res.init();
-        res
+        Self::from_seed(Seed::new(seed))
     }
 
-    pub fn init(&mut self) {
-        self.init_mpriv();
-        self.init_mpub();
+    pub fn from_seed(seed: Seed) -> Self {
+        let key = KeyWallet::new(SecretKey::parse_slice(&seed[0..32]).unwrap(), true);
+        ChildWallet {
+            seed,
+            key,
+            children: BTreeMap::new(),
+        }
     }
 
     pub fn mpriv_bits(&self) -> &[u8] {
@@ -50,322 +45,245 @@ impl ChildWallet {
         &self.seed[32..64]
     }
 
-    fn init_mpriv(&mut self) {
-        self.mpriv = Some(SecretKey::parse_slice(self.mpriv_bits()).unwrap());
+    /// the key of this node, for signing and nonces
+    pub fn key(&self) -> &KeyWallet {
+        &self.key
     }
 
-    pub fn mpriv(&self) -> &SecretKey {
-        self.mpriv.as_ref().expect("wallet uninitialized")
+    pub fn key_mut(&mut self) -> &mut KeyWallet {
+        &mut self.key
     }
 
-    fn init_mpub(&mut self) {
-        self.mpub = Some(PublicKey::from_secret_key(self.mpriv()));
+    pub fn mpriv(&self) -> &SecretKey {
+        self.key.mpriv()
     }
 
     pub fn mpub(&self) -> &PublicKey {
-        self.mpub.as_ref().expect("wallet uninitialized")
+        self.key.mpub()
     }
 
-    pub fn nonce(&mut self) -> u8 {
-        self.nonce = self.nonce.wrapping_add(1);
-        self.nonce
+    pub fn next_child(&mut self, hardened: bool) -> Result<&mut ChildWallet, Error> {
+        let next = self
+            .children
+            .keys()
+            .filter(|c| c.is_hardened() == hardened)
+            .next_back()
+            .map(|c| c.index() + 1)
+            .unwrap_or(0);
+        self.load_child(next, hardened)
     }
 
-    pub fn next_child(&mut self, hardened: bool) -> Result<&mut ChildWallet, Error> {
-        if !hardened {
-            self.load_child(self.children.len() as u32, false)
+    /// child `number` of this key, without loading it
+    pub(super) fn derive_child(&self, number: ChildNumber) -> Result<ChildWallet, Error> {
+        let mut mac =
+            HmacSha512::new_varkey(self.chain_code()).map_err(|e| format_err!("{}", e))?;
+        // sized up front, so no reallocation leaves a copy of the key behind
+        let mut v = Secret::new(Vec::with_capacity(37));
+        if number.is_hardened() {
+            v.push(0x0);
+            v.extend_from_slice(&Secret::new(self.mpriv().serialize())[..]);
         } else {
-            self.load_child(self.hardened_children.len() as u32, true)
+            v.extend_from_slice(&self.mpub().serialize_compressed());
+        }
+        v.extend_from_slice(&number.to_u32().to_be_bytes());
+        mac.input(&v);
+        let mut l = super::mac_to_seed(mac);
+        let ll: Scalar = SecretKey::parse_slice(&l[0..32])
+            .map_err(|e| format_err!("{:?}", e))?
+            .into();
+        let cpriv = ll + self.mpriv().clone().into();
+        let cpriv_bytes = Secret::new(cpriv.b32());
+        for n in 0..32 {
+            l[n] = cpriv_bytes[n];
         }
+        Ok(ChildWallet::from_seed(l))
     }
 
     pub fn load_child(&mut self, i: u32, hardened: bool) -> Result<&mut ChildWallet, Error> {
-        if !hardened {
-            let min_len = i + 1;
-            if (self.children.len() as u32) < min_len {
-                self.children.resize(min_len as usize, None);
-            }
-
-            if self.children[i as usize].is_none() {
-                let mut mac =
-                    HmacSha512::new_varkey(self.chain_code()).map_err(|e| format_err!("{}", e))?;
-                let mut v = self.mpub().serialize_compressed().to_vec();
-                v.extend(&i.to_be_bytes());
-                mac.input(&v);
-                let mut l: [u8; 64] = [0; 64];
-                l.clone_from_slice(mac.result().code().as_slice());
-                let ll: Scalar = SecretKey::parse_slice(&l[0..32])
-                    .map_err(|e| format_err!("{:?}", e))?
-                    .into();
-                let cpriv = ll + self.mpriv().clone().into();
-                let cpriv_bytes = cpriv.b32();
-                for n in 0..32 {
-                    l[n] = cpriv_bytes[n];
-                }
-                self.children[i as usize] = Some(ChildWallet::new(l));
-            }
+        let number = ChildNumber::new(i, hardened)?;
+        if !self.children.contains_key(&number) {
+            let child = self.derive_child(number)?;
+            self.children.insert(number, child);
+        }
 
-            Ok(self.children[i as usize].as_mut().unwrap())
-        } else {
-            let hardened_i: u32 = 2_u32.pow(31) + i;
-            let min_len = i + 1;
-            if (self.hardened_children.len() as u32) < min_len {
-                self.hardened_children.resize(min_len as usize, None);
-            }
+        Ok(self.children.get_mut(&number).unwrap())
+    }
 
-            if self.hardened_children[i as usize].is_none() {
-                let mut mac =
-                    HmacSha512::new_varkey(self.chain_code()).map_err(|e| format_err!("{}", e))?;
-                let mut v = [&[0x0], &self.mpriv().serialize()[..]].concat().to_vec();
-                v.extend(&hardened_i.to_be_bytes());
-                mac.input(&v);
-                let mut l: [u8; 64] = [0; 64];
-                l.clone_from_slice(mac.result().code().as_slice());
-                let ll: Scalar = SecretKey::parse_slice(&l[0..32])
-                    .map_err(|e| format_err!("{:?}", e))?
-                    .into();
-                let cpriv = ll + self.mpriv().clone().into();
-                let cpriv_bytes = cpriv.b32();
-                for n in 0..32 {
-                    l[n] = cpriv_bytes[n];
-                }
-                self.hardened_children[i as usize] = Some(ChildWallet::new(l));
-            }
+    pub fn derive(&mut self, path: &DerivationPath) -> Result<&mut ChildWallet, Error> {
+        let mut cur = self;
+        for c in path.iter() {
+            cur = cur.load_child(c.index(), c.is_hardened())?;
+        }
+        Ok(cur)
+    }
 
-            Ok(self.hardened_children[i as usize].as_mut().unwrap())
+    /// the key at `path` below this one, derived without loading it or any
+    /// key on the way, so it is not persisted
+    pub fn peek(&self, path: &DerivationPath) -> Result<ChildWallet, Error> {
+        let mut cur = ChildWallet::from_seed(self.seed.clone());
+        for c in path.iter() {
+            cur = cur.derive_child(*c)?;
         }
+        Ok(cur)
     }
 
     pub fn get_child(&self, i: u32, hardened: bool) -> Option<&ChildWallet> {
-        if !hardened {
-            self.children.get(i as usize).and_then(|a| a.as_ref())
-        } else {
-            self.hardened_children
-                .get(i as usize)
-                .and_then(|a| a.as_ref())
-        }
+        self.children.get(&ChildNumber::new(i, hardened).ok()?)
     }
 
     pub fn pubkey_hash(&self) -> Vec<u8> {
-        let mut hasher = Sha256::new();
-        hasher.input(&self.mpub().serialize_compressed()[..]);
-        let sha_bytes = hasher.result();
-
-        let mut hasher = Ripemd160::new();
-        hasher.input(&sha_bytes);
-        let ripe_bytes = hasher.result();
-
-        ripe_bytes.to_vec()
+        self.key.pubkey_hash()
     }
 
     pub fn address(&self, network: Network) -> String {
-        pubkey_hash_to_addr(&self.pubkey_hash(), network)
+        self.key.address(network)
     }
 
-    fn serializable(&self) -> Result<SerializableChildWallet, Error> {
-        let seed = self.seed.clone();
-        let mpriv = self.mpriv.clone().map(|k| k.serialize());
-        let mpub = self.mpub.clone().map(|k| k.serialize());
-        let children: Vec<Option<ByteVec>> = self
-            .children
-            .iter()
-            .map(|c| match c {
-                Some(c) => Ok(Some(ByteVec(c.as_bytes()?))),
-                None => Ok(None),
-            })
-            .collect::<Result<Vec<Option<ByteVec>>, Error>>()?;
-
-        let hardened_children: Vec<Option<ByteVec>> = self
-            .hardened_children
-            .iter()
-            .map(|c| match c {
-                Some(c) => Ok(Some(ByteVec(c.as_bytes()?))),
-                None => Ok(None),
-            })
-            .collect::<Result<Vec<Option<ByteVec>>, Error>>()?;
-
-        Ok(SerializableChildWallet {
-            seed,
-            mpriv,
-            mpub,
-            children,
-            hardened_children,
-            nonce: self.nonce,
-        })
+    pub fn script(&self) -> bitcoin::Script {
+        self.key.script()
     }
 
-    fn from_serializable(w: SerializableChildWallet) -> Result<Self, Error> {
-        let seed = w.seed;
-
-        let mpriv = match w.mpriv {
-            Some(data) => Some(SecretKey::parse(&data).map_err(|e| format_err!("{:?}", e))?),
-            None => None,
-        };
+    pub fn to_wif(&self, network: Network) -> String {
+        self.key.to_wif(network)
+    }
 
-        let mpub = match w.mpub {
-            Some(data) => Some(PublicKey::parse(&data).map_err(|e| format_err!("{:?}", e))?),
-            None => None,
-        };
+    /// the key alone, without the chain code
+    pub fn to_key_wallet(&self) -> KeyWallet {
+        self.key.clone()
+    }
 
-        let children: Vec<Option<ChildWallet>> = w
-            .children
-            .iter()
-            .map(|c| match c {
-                Some(ByteVec(ref c)) => Ok(Some(ChildWallet::from_bytes(c)?)),
-                None => Ok(None),
-            })
-            .collect::<Result<Vec<Option<ChildWallet>>, Error>>()?;
-
-        let hardened_children: Vec<Option<ChildWallet>> = w
-            .hardened_children
-            .iter()
-            .map(|c| match c {
-                Some(ByteVec(ref c)) => Ok(Some(ChildWallet::from_bytes(c)?)),
-                None => Ok(None),
-            })
-            .collect::<Result<Vec<Option<ChildWallet>>, Error>>()?;
-
-        Ok(ChildWallet {
-            seed,
-            mpriv,
-            mpub,
-            children,
-            hardened_children,
-            nonce: w.nonce,
-        })
+    /// every loaded key below (and including) this one, relative to it
+    pub(super) fn keys(&self) -> Vec<SerializableKey> {
+        let mut res = Vec::new();
+        self.collect_keys(&DerivationPath::master(), &mut res);
+        res
     }
 
-    pub fn as_bytes(&self) -> Result<Vec<u8>, Error> {
-        Ok(serde_cbor::to_vec(&self.serializable()?)?)
+    fn collect_keys(&self, path: &DerivationPath, res: &mut Vec<SerializableKey>) {
+        // keys on the way to another are derived again when it is restored, so
+        // they only need recording if they have metadata of their own
+        if self.children.is_empty() || self.key.nonce_counter() != 0 {
+            res.push(SerializableKey {
+                path: path.clone(),
+                nonce_counter: self.key.nonce_counter(),
+            });
+        }
+        for (number, child) in self.children.iter() {
+            child.collect_keys(&path.child(*number), res);
+        }
     }
 
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
-        let w: SerializableChildWallet = serde_cbor::from_slice(bytes)?;
-        Self::from_serializable(w)
+    /// re-derives every key listed by `keys` and restores its metadata
+    pub(super) fn restore_keys(&mut self, keys: &[SerializableKey]) -> Result<(), Error> {
+        for key in keys {
+            self.derive(&key.path)?
+                .key
+                .set_nonce_counter(key.nonce_counter);
+        }
+        Ok(())
     }
 
-    pub fn script(&self) -> bitcoin::Script {
-        addr_to_script(&self.address(Network::Bitcoin), Network::Bitcoin).unwrap()
-    }
-
-    pub fn construct_signed(
-        &self,
-        inputs: &[Vec<u8>],
-        outputs: &[(&str, u64)],
-        fee: u64,
-        op_return: Option<&[u8]>,
-        network: Network,
-    ) -> Result<Vec<u8>, Error> {
-        use bitcoin::consensus::Decodable;
-        use bitcoin::consensus::Encodable;
-        use bitcoin::{OutPoint, Transaction, TxIn, TxOut};
-        use bitcoin_hashes::Hash;
-        use std::io::Cursor;
-
-        let script = self.script();
-
-        let inputs = inputs
-            .into_iter()
-            .map(|i| Transaction::consensus_decode(&mut Cursor::new(i)))
-            .collect::<Result<HashSet<_>, _>>()?
-            .into_iter()
-            .flat_map(|tx| {
-                tx.output
-                    .clone()
-                    .into_iter()
-                    .enumerate()
-                    .filter(|(_, o)| o.script_pubkey == script)
-                    .map(|(vout, o)| {
-                        (
-                            OutPoint {
-                                txid: tx.txid(),
-                                vout: vout as u32,
-                            },
-                            o,
-                        )
-                    })
-                    .collect::<Vec<_>>()
-            })
-            .collect::<Vec<_>>();
-        let input_size = inputs.iter().fold(0, |acc, tx| acc + tx.1.value);
-        let output_size = outputs.iter().fold(0, |acc, o| acc + o.1);
-        if output_size > input_size - fee || input_size < fee {
-            bail!("insufficient funds")
+    fn serializable(&self) -> SerializableChildWallet {
+        SerializableChildWallet {
+            seed: self.seed.to_vec(),
+            keys: self.keys(),
         }
-        let mut outputs = outputs.iter().cloned().collect::<Vec<_>>();
-        let address = self.address(network);
-        if input_size - output_size - fee > fee {
-            outputs.push((address.as_str(), input_size - output_size - fee));
+    }
+
+    fn from_serializable(w: SerializableChildWallet) -> Result<Self, Error> {
+        ensure!(w.seed.len() == 64, "invalid seed length");
+        let mut seed = Seed::new([0; 64]);
+        seed.clone_from_slice(&w.seed);
+        let mut res = ChildWallet::from_seed(seed);
+        res.restore_keys(&w.keys)?;
+        Ok(res)
+    }
+
+    fn from_legacy(mut w: LegacyChildWallet) -> Result<Self, Error> {
+        let mut res = ChildWallet::from_seed(Seed::new(w.seed));
+        let lists = vec![
+            (false, std::mem::take(&mut w.children)),
+            (true, std::mem::take(&mut w.hardened_children)),
+        ];
+        for (hardened, list) in lists {
+            for (i, c) in list.into_iter().enumerate() {
+                if let Some(ByteVec(ref c)) = c {
+                    res.children.insert(
+                        ChildNumber::new(i as u32, hardened)?,
+                        ChildWallet::from_bytes(c)?,
+                    );
+                }
+            }
         }
 
-        let output = outputs
-            .into_iter()
-            .map(|(addr, val)| -> Result<_, Error> {
-                Ok(TxOut {
-                    script_pubkey: addr_to_script(addr, network)?,
-                    value: val,
-                })
-            })
-            .chain(op_return.into_iter().map(|data| -> Result<_, Error> {
-                let mut s: Vec<u8> = vec![0x6a, 0x4c, data.len() as u8];
-                s.extend(data.iter());
-                Ok(TxOut {
-                    script_pubkey: bitcoin::Script::from(s),
-                    value: 0,
-                })
-            }))
-            .collect::<Result<Vec<_>, Error>>()?;
-        let input: Vec<TxIn> = inputs
-            .into_iter()
-            .map(|a| TxIn {
-                previous_output: a.0,
-                script_sig: bitcoin::Script::new(),
-                sequence: 0xFFFFFFFF_u32,
-                witness: vec![],
-            })
-            .collect();
-        let mut tx = Transaction {
-            version: 1,
-            lock_time: 0,
-            input: input.clone(),
-            output,
-        };
-        tx.input = input
-            .into_iter()
-            .enumerate()
-            .map(|(i, vin)| -> Result<_, Error> {
-                let sighash = tx.signature_hash(i, &script, 0x01).into_inner();
-                let (sig, _) = secp256k1::sign(&secp256k1::Message::parse(&sighash), self.mpriv());
-                let pubkey = self.mpub().serialize_compressed();
-                let sig_der = sig.serialize_der();
-                let script_sig = bitcoin::Script::from(
-                    [
-                        &[sig_der.as_ref().len() as u8 + 1][..],
-                        sig_der.as_ref(),
-                        &[0x01, pubkey.len() as u8][..],
-                        &pubkey[..],
-                    ]
-                    .concat(),
-                );
-                Ok(TxIn {
-                    previous_output: vin.previous_output,
-                    script_sig,
-                    sequence: vin.sequence,
-                    witness: vin.witness,
-                })
-            })
-            .collect::<Result<Vec<_>, Error>>()?;
-        let mut res = Vec::new();
-        tx.consensus_encode(&mut res)?;
+        // carry on from the last nonce the legacy wallet used
+        let nonce_counter =
+            nonce::counter_of(nonce::key_offset(res.mpriv()), w.nonce.wrapping_add(1));
+        res.key.set_nonce_counter(nonce_counter);
         Ok(res)
     }
+
+    pub fn as_bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(serde_cbor::ser::to_vec_packed(&self.serializable())?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if is_legacy(bytes) {
+            Self::from_legacy(serde_cbor::from_slice(bytes)?)
+        } else {
+            Self::from_serializable(serde_cbor::from_slice(bytes)?)
+        }
+    }
+
+    pub fn watch_only(&self) -> WatchOnlyWallet {
+        // a seed always holds a whole chain code
+        WatchOnlyWallet::from_parts(self.mpub().clone(), self.chain_code()).unwrap()
+    }
+}
+impl std::fmt::Debug for ChildWallet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChildWallet")
+            .field("seed", &self.seed)
+            .field(
+                "mpub",
+                &hex::encode(&self.mpub().serialize_compressed()[..]),
+            )
+            .field("children", &self.children)
+            .field("nonce_counter", &self.key.nonce_counter())
+            .finish()
+    }
 }
 
 #[derive(Deserialize, Serialize)]
 struct ByteVec(#[serde(with = "serde_bytes")] pub Vec<u8>);
+impl Drop for ByteVec {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SerializableKey {
+    path: DerivationPath,
+    nonce_counter: u32,
+}
 
 #[derive(Deserialize, Serialize)]
 pub struct SerializableChildWallet {
+    #[serde(with = "serde_bytes")]
+    seed: Vec<u8>,
+    keys: Vec<SerializableKey>,
+}
+impl Drop for SerializableChildWallet {
+    fn drop(&mut self) {
+        self.seed.zeroize();
+    }
+}
+
+/// format written before keys were re-derived on load: every child embedded
+/// its own seed, keys and children
+#[derive(Deserialize, Serialize)]
+pub struct LegacyChildWallet {
     #[serde(with = "BigArray")]
     seed: [u8; 64],
     mpriv: Option<[u8; 32]>,
@@ -375,3 +293,11 @@ pub struct SerializableChildWallet {
     hardened_children: Vec<Option<ByteVec>>,
     nonce: u8,
 }
+impl Drop for LegacyChildWallet {
+    fn drop(&mut self) {
+        self.seed.zeroize();
+        if let Some(ref mut key) = self.mpriv {
+            key.zeroize();
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use super::ChildNumber;
+use super::ChildWallet;
+use super::DerivationPath;
+use crate::Network;
+use failure::Error;
+
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoveredAddress {
+    pub path: String,
+    pub address: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainDiscovery {
+    pub used: Vec<DiscoveredAddress>,
+    pub next_unused: DiscoveredAddress,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountDiscovery {
+    pub account: u32,
+    pub path: String,
+    pub external: ChainDiscovery,
+    pub change: ChainDiscovery,
+}
+
+/// Scans a single chain (`m/.../chain`) until `gap_limit` consecutive
+/// addresses fail `is_used`.
+fn scan_chain<F: FnMut(&str) -> bool>(
+    chain: &ChildWallet,
+    path: &DerivationPath,
+    gap_limit: u32,
+    network: Network,
+    is_used: &mut F,
+) -> Result<ChainDiscovery, Error> {
+    let mut used = Vec::new();
+    let mut next_unused = None;
+    let mut gap = 0;
+    let mut i = 0;
+    while gap < gap_limit {
+        let addr = DiscoveredAddress {
+            path: path.child(ChildNumber::new(i, false)?).to_string(),
+            address: chain
+                .derive_child(ChildNumber::new(i, false)?)?
+                .address(network),
+        };
+        if is_used(&addr.address) {
+            used.push(addr);
+            next_unused = None;
+            gap = 0;
+        } else {
+            if next_unused.is_none() {
+                next_unused = Some(addr);
+            }
+            gap += 1;
+        }
+        i += 1;
+    }
+
+    Ok(ChainDiscovery {
+        used,
+        next_unused: next_unused.ok_or(format_err!("gap limit must be greater than 0"))?,
+    })
+}
+
+/// BIP44 account discovery: accounts are scanned in order until one is found
+/// with no used external addresses. The first account is always reported so
+/// that a fresh wallet still has a receive address. Nothing scanned is loaded
+/// into `parent`, so probing past the gap does not grow what it persists.
+pub fn discover<F: FnMut(&str) -> bool>(
+    parent: &ChildWallet,
+    network: Network,
+    gap_limit: u32,
+    mut is_used: F,
+) -> Result<Vec<AccountDiscovery>, Error> {
+    let mut res = Vec::new();
+    for account in 0.. {
+        let path = DerivationPath::from(vec![
+            ChildNumber::new(44, true)?,
+            ChildNumber::new(network.coin_type(), true)?,
+            ChildNumber::new(account, true)?,
+        ]);
+        let external_path = path.child(ChildNumber::new(0, false)?);
+        let external = scan_chain(
+            &parent.peek(&external_path)?,
+            &external_path,
+            gap_limit,
+            network,
+            &mut is_used,
+        )?;
+        let change_path = path.child(ChildNumber::new(1, false)?);
+        let change = scan_chain(
+            &parent.peek(&change_path)?,
+            &change_path,
+            gap_limit,
+            network,
+            &mut is_used,
+        )?;
+        let empty = external.used.is_empty();
+        if !empty || account == 0 {
+            res.push(AccountDiscovery {
+                account,
+                path: path.to_string(),
+                external,
+                change,
+            });
+        }
+        if empty {
+            break;
+        }
+    }
+
+    Ok(res)
+}
@@ -0,0 +1,118 @@
+use super::Secret;
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use failure::Error;
+
+pub const MAGIC: [u8; 4] = *b"BRKW";
+pub const VERSION: u8 = 1;
+
+/// scrypt cost parameters written to the header, so they can be raised
+/// later without breaking existing buffers
+pub const DEFAULT_LOG_N: u8 = 15;
+pub const DEFAULT_R: u32 = 8;
+pub const DEFAULT_P: u32 = 1;
+/// scrypt needs 128 * r * 2^log_n bytes; refuse headers asking for more
+/// than 1 GiB
+const MAX_KDF_MEMORY: u64 = 1 << 30;
+/// the largest r fitting in that memory at all
+const MAX_R: u32 = 1 << 22;
+const MAX_P: u32 = 16;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+/// magic, version, log_n, r, p, salt, nonce
+const HEADER_LEN: usize = 4 + 1 + 1 + 4 + 4 + SALT_LEN + NONCE_LEN;
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+fn derive_key(
+    password: &str,
+    salt: &[u8],
+    log_n: u8,
+    r: u32,
+    p: u32,
+) -> Result<Secret<[u8; 32]>, Error> {
+    let params =
+        scrypt::ScryptParams::new(log_n, r, p).map_err(|_| format_err!("invalid kdf params"))?;
+    let mut key = Secret::new([0; 32]);
+    scrypt::scrypt(password.as_bytes(), salt, &params, &mut *key)
+        .map_err(|_| format_err!("invalid kdf output length"))?;
+    Ok(key)
+}
+
+pub fn encrypt(plaintext: &[u8], password: &str) -> Result<Vec<u8>, Error> {
+    use rand::RngCore;
+
+    let mut salt: [u8; SALT_LEN] = [0; SALT_LEN];
+    let mut nonce: [u8; NONCE_LEN] = [0; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let mut res = Vec::with_capacity(HEADER_LEN + plaintext.len() + 16);
+    res.extend_from_slice(&MAGIC);
+    res.push(VERSION);
+    res.push(DEFAULT_LOG_N);
+    res.extend_from_slice(&DEFAULT_R.to_be_bytes());
+    res.extend_from_slice(&DEFAULT_P.to_be_bytes());
+    res.extend_from_slice(&salt);
+    res.extend_from_slice(&nonce);
+
+    let key = derive_key(password, &salt, DEFAULT_LOG_N, DEFAULT_R, DEFAULT_P)?;
+    let cipher = ChaCha20Poly1305::new(&Key::from(*key));
+    let ciphertext = cipher
+        .encrypt(
+            &Nonce::from(nonce),
+            Payload {
+                msg: plaintext,
+                aad: &res,
+            },
+        )
+        .map_err(|_| format_err!("encryption failed"))?;
+    res.extend(ciphertext);
+
+    Ok(res)
+}
+
+pub fn decrypt(data: &[u8], password: &str) -> Result<Vec<u8>, Error> {
+    use std::convert::TryInto;
+
+    if !is_encrypted(data) {
+        bail!("buffer is not encrypted");
+    }
+    ensure!(data.len() > HEADER_LEN, "unexpected end of input");
+    if data[4] != VERSION {
+        bail!("unsupported encryption version {}", data[4]);
+    }
+    let log_n = data[5];
+    let r = u32::from_be_bytes(data[6..10].try_into()?);
+    let p = u32::from_be_bytes(data[10..14].try_into()?);
+    ensure!(r > 0 && p > 0, "invalid kdf params");
+    // read before authentication, so nothing here may overflow
+    let memory = 1u64
+        .checked_shl(log_n as u32)
+        .and_then(|n| n.checked_mul(128))
+        .and_then(|m| m.checked_mul(r as u64));
+    ensure!(
+        log_n < 32
+            && r <= MAX_R
+            && p <= MAX_P
+            && memory.map(|m| m <= MAX_KDF_MEMORY).unwrap_or(false),
+        "kdf cost exceeds maximum"
+    );
+    let salt = &data[14..14 + SALT_LEN];
+    let nonce: [u8; NONCE_LEN] = data[14 + SALT_LEN..HEADER_LEN].try_into()?;
+
+    let key = derive_key(password, salt, log_n, r, p)?;
+    let cipher = ChaCha20Poly1305::new(&Key::from(*key));
+    cipher
+        .decrypt(
+            &Nonce::from(nonce),
+            Payload {
+                msg: &data[HEADER_LEN..],
+                aad: &data[..HEADER_LEN],
+            },
+        )
+        .map_err(|_| format_err!("incorrect password"))
+}
@@ -0,0 +1,56 @@
+//! Why a transaction could not be built, in enough detail for a UI to say
+//! what to do about it rather than only that something went wrong.
+
+use crate::Network;
+
+#[derive(Clone, Debug, PartialEq, Eq, Fail, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum TxBuildError {
+    /// the inputs given are worth less than the outputs plus the fee
+    #[fail(
+        display = "insufficient funds: {} needed, {} available",
+        needed, available
+    )]
+    InsufficientFunds { needed: u64, available: u64 },
+    /// none of the outputs given can be spent, e.g. none pay to the sender
+    #[fail(display = "no inputs to spend: {}", reason)]
+    NoMatchingInputs { reason: String },
+    /// an output given to spend pays to none of the signing keys
+    #[fail(display = "output {}:{} is not spendable by this wallet", txid, vout)]
+    ForeignInput { txid: String, vout: u32 },
+    #[fail(
+        display = "output {} of value {} is below the dust threshold of {}",
+        index, value, threshold
+    )]
+    DustOutput {
+        index: usize,
+        value: u64,
+        threshold: u64,
+    },
+    #[fail(display = "OP_RETURN of {} bytes is over the maximum of {}", len, max)]
+    OpReturnTooLarge { len: usize, max: usize },
+    /// signed, the transaction would be too large to relay
+    #[fail(
+        display = "transaction of {} bytes is over the maximum of {}",
+        size, max
+    )]
+    TooLarge { size: usize, max: usize },
+    #[fail(display = "invalid {:?} address {}: {}", network, address, reason)]
+    InvalidAddress {
+        address: String,
+        /// the caller knows which network it asked for
+        #[serde(skip)]
+        network: Network,
+        reason: String,
+    },
+    /// each transaction of a chain spends the change of the one before, so it
+    /// cannot go to another address
+    #[fail(
+        display = "a chain of {} transactions must send its change back to the sender",
+        txs
+    )]
+    ChainedChange { txs: usize },
+    /// values adding up to more than a u64 holds
+    #[fail(display = "{} value overflow", what)]
+    Overflow { what: &'static str },
+}
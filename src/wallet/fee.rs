@@ -0,0 +1,137 @@
+//! Fees computed from an estimate of the signed transaction size, so borks
+//! with many inputs or mentions pay their way and small ones don't overpay.
+
+use crate::Network;
+
+/// DER signatures are at most 72 bytes, plus the sighash type
+const MAX_SIG_LEN: usize = 73;
+
+/// larger transactions are not relayed, on any of the networks
+pub const MAX_STANDARD_TX_SIZE: usize = 100_000;
+
+/// room kept in a standard transaction for everything but its OP_RETURN, enough
+/// for dozens of inputs and outputs
+const NON_OP_RETURN_SIZE: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Fee {
+    /// a fixed amount, whatever the size
+    Flat(u64),
+    /// per byte of the signed transaction
+    PerByte(u64),
+    /// per started kilobyte, the way Dogecoin charges
+    PerKb(u64),
+}
+impl Fee {
+    /// the fee for a transaction of `size` bytes, raised to the network's
+    /// minimum relay fee unless it is flat
+    pub fn amount(&self, size: usize, network: Network) -> u64 {
+        let size = size as u64;
+        match *self {
+            Fee::Flat(fee) => fee,
+            Fee::PerByte(rate) => rate.saturating_mul(size).max(min_relay_fee(size, network)),
+            Fee::PerKb(rate) => rate
+                .saturating_mul(started_kb(size))
+                .max(min_relay_fee(size, network)),
+        }
+    }
+}
+
+fn started_kb(size: u64) -> u64 {
+    size.div_ceil(1000)
+}
+
+/// Dogecoin relays at 0.001 DOGE per started kilobyte, Bitcoin and Litecoin at
+/// 1000 satoshis per kilobyte
+pub fn min_relay_fee(size: u64, network: Network) -> u64 {
+    match network {
+        Network::Dogecoin => 100_000 * started_kb(size),
+        Network::Litecoin | Network::Bitcoin => size,
+    }
+}
+
+/// Outputs worth less than this are not relayed. Dogecoin uses a flat 0.01
+/// DOGE, Bitcoin and Litecoin the cost of creating and spending the output at
+/// their dust relay fees of 3000 and 30000 satoshis per kilobyte.
+pub fn dust_threshold(script_len: usize, network: Network) -> u64 {
+    // output plus a p2pkh input spending it
+    let spend_size = (8 + var_int_len(script_len) + script_len + 148) as u64;
+    match network {
+        Network::Dogecoin => 1_000_000,
+        Network::Litecoin => spend_size * 30,
+        Network::Bitcoin => spend_size * 3,
+    }
+}
+
+/// The largest OP_RETURN script, opcodes included, relayed by default. This is
+/// `-datacarriersize`: 83 bytes for Dogecoin and Litecoin, 100000 since
+/// Bitcoin Core 30.
+pub fn data_carrier_size(network: Network) -> usize {
+    match network {
+        Network::Dogecoin | Network::Litecoin => 83,
+        Network::Bitcoin => 100_000,
+    }
+}
+
+/// the most data an OP_RETURN may carry with `data_carrier_size`, capped so the
+/// transaction carrying it stays standard; on Bitcoin the transaction size
+/// rather than the data carrier size is the limit
+pub fn max_relayed_op_return_len(data_carrier_size: usize) -> usize {
+    max_op_return_len(data_carrier_size.min(MAX_STANDARD_TX_SIZE - NON_OP_RETURN_SIZE))
+}
+
+/// the most data an OP_RETURN script of `script_len` bytes holds when pushed
+/// with the smallest opcode
+fn max_op_return_len(script_len: usize) -> usize {
+    [1, 2, 3, 5]
+        .iter()
+        .filter_map(|overhead| script_len.checked_sub(1 + overhead))
+        .filter(|len| 1 + push_len(*len) + len <= script_len)
+        .max()
+        .unwrap_or(0)
+}
+
+fn var_int_len(n: usize) -> usize {
+    match n {
+        0..=0xFC => 1,
+        0xFD..=0xFFFF => 3,
+        0x10000..=0xFFFF_FFFF => 5,
+        _ => 9,
+    }
+}
+
+fn push_len(len: usize) -> usize {
+    match len {
+        0..=75 => 1,
+        76..=0xFF => 2,
+        0x100..=0xFFFF => 3,
+        _ => 5,
+    }
+}
+
+/// signed p2pkh scriptSig: `<sig> <pubkey>`
+pub fn p2pkh_script_sig_len(pubkey_len: usize) -> usize {
+    1 + MAX_SIG_LEN + 1 + pubkey_len
+}
+
+/// signed p2sh multisig scriptSig: `OP_0 <sig>... <redeem script>`
+pub fn multisig_script_sig_len(threshold: usize, redeem_script_len: usize) -> usize {
+    1 + threshold * (1 + MAX_SIG_LEN) + push_len(redeem_script_len) + redeem_script_len
+}
+
+/// serialized size of a transaction spending `inputs` outputs with signed
+/// scriptSigs of `script_sig_len` bytes, to outputs with scripts of the given
+/// lengths
+pub fn estimate_size(inputs: usize, script_sig_len: usize, output_scripts: &[usize]) -> usize {
+    let input_len = 32 + 4 + var_int_len(script_sig_len) + script_sig_len + 4;
+    let output_len = output_scripts
+        .iter()
+        .map(|len| 8 + var_int_len(*len) + len)
+        .sum::<usize>();
+    4 + var_int_len(inputs)
+        + inputs * input_len
+        + var_int_len(output_scripts.len())
+        + output_len
+        + 4
+}
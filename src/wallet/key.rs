@@ -0,0 +1,363 @@
+use super::addr_to_script;
+use super::amount::Amount;
+use super::bump;
+use super::chain::{self, BorkPlan};
+use super::fee::{self, Fee};
+use super::hash160;
+use super::message;
+use super::nonce;
+use super::psbt;
+use super::pubkey_to_addr;
+use super::tx::{self, BuiltTx, Sender, TxOptions, Utxo};
+use super::verify;
+use super::Secret;
+use crate::Network;
+use failure::Error;
+use secp256k1::{PublicKey, SecretKey};
+
+pub fn wif_version(network: Network) -> u8 {
+    match network {
+        Network::Dogecoin => 0x9E,
+        Network::Litecoin => 0xB0,
+        Network::Bitcoin => 0x80,
+    }
+}
+
+pub fn privkey_to_wif(key: &SecretKey, compressed: bool, network: Network) -> String {
+    use bitcoin::util::base58;
+
+    // sized up front, so no reallocation leaves a copy of the key behind
+    let mut data = Secret::new(Vec::with_capacity(34));
+    data.push(wif_version(network));
+    data.extend_from_slice(&Secret::new(key.serialize())[..]);
+    if compressed {
+        data.push(0x01);
+    }
+    base58::check_encode_slice(&data)
+}
+
+pub fn wif_to_privkey(wif: &str, network: Network) -> Result<(SecretKey, bool), Error> {
+    use bitcoin::util::base58;
+
+    let data = Secret::new(base58::from_check(wif)?);
+    let compressed = match data.len() {
+        33 => false,
+        34 if data[33] == 0x01 => true,
+        _ => bail!("invalid WIF length"),
+    };
+    if data[0] != wif_version(network) {
+        bail!("WIF version {:x} does not match network", data[0]);
+    }
+    let key = SecretKey::parse_slice(&data[1..33]).map_err(|e| format_err!("{:?}", e))?;
+    Ok((key, compressed))
+}
+
+/// A wallet for a single imported private key, e.g. from Dogecoin Core's
+/// `dumpprivkey`. Unlike `ChildWallet` it has no chain code, so it cannot
+/// derive children.
+#[derive(Clone)]
+pub struct KeyWallet {
+    mpriv: SecretKey,
+    mpub: PublicKey,
+    compressed: bool,
+    nonce_counter: u32,
+}
+impl KeyWallet {
+    pub fn new(mpriv: SecretKey, compressed: bool) -> Self {
+        KeyWallet {
+            mpub: PublicKey::from_secret_key(&mpriv),
+            mpriv,
+            compressed,
+            nonce_counter: 0,
+        }
+    }
+
+    pub fn from_wif(wif: &str, network: Network) -> Result<Self, Error> {
+        let (mpriv, compressed) = wif_to_privkey(wif, network)?;
+        Ok(Self::new(mpriv, compressed))
+    }
+
+    pub fn to_wif(&self, network: Network) -> String {
+        privkey_to_wif(self.mpriv(), self.compressed, network)
+    }
+
+    pub fn mpriv(&self) -> &SecretKey {
+        &self.mpriv
+    }
+
+    pub fn mpub(&self) -> &PublicKey {
+        &self.mpub
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    pub fn nonce(&mut self) -> u8 {
+        let res = nonce::derive(nonce::key_offset(self.mpriv()), self.nonce_counter);
+        self.nonce_counter = self.nonce_counter.wrapping_add(1);
+        res
+    }
+
+    /// the nonce `nonce` returns next, without using it up
+    pub fn next_nonce(&self) -> u8 {
+        nonce::derive(nonce::key_offset(self.mpriv()), self.nonce_counter)
+    }
+
+    pub fn nonce_counter(&self) -> u32 {
+        self.nonce_counter
+    }
+
+    pub(super) fn set_nonce_counter(&mut self, nonce_counter: u32) {
+        self.nonce_counter = nonce_counter;
+    }
+
+    pub fn sync_nonce(&mut self, observed: &[u8]) {
+        self.nonce_counter = nonce::sync_counter(
+            nonce::key_offset(self.mpriv()),
+            self.nonce_counter,
+            observed,
+        );
+    }
+
+    pub fn pubkey(&self) -> Vec<u8> {
+        if self.compressed {
+            self.mpub.serialize_compressed().to_vec()
+        } else {
+            self.mpub.serialize().to_vec()
+        }
+    }
+
+    pub fn pubkey_hash(&self) -> Vec<u8> {
+        hash160(&self.pubkey())
+    }
+
+    pub fn address(&self, network: Network) -> String {
+        pubkey_to_addr(&self.pubkey(), network)
+    }
+
+    pub fn sign_message(&self, message: &str, network: Network) -> Result<String, Error> {
+        message::sign_message(self.mpriv(), self.compressed, message, network)
+    }
+
+    pub fn script(&self) -> bitcoin::Script {
+        addr_to_script(&self.address(Network::Bitcoin), Network::Bitcoin).unwrap()
+    }
+
+    /// the size of the scriptSig spending an output of `script`, for fee estimates
+    pub fn script_sig_len(&self) -> usize {
+        fee::p2pkh_script_sig_len(self.pubkey().len())
+    }
+
+    pub fn construct_signed(
+        &self,
+        inputs: &[Vec<u8>],
+        outputs: &[(&str, Amount)],
+        fee: Amount,
+        op_return: Option<&[u8]>,
+        network: Network,
+    ) -> Result<Vec<u8>, Error> {
+        self.build_signed(
+            &Utxo::from_txs(inputs, &self.script())?,
+            outputs,
+            op_return,
+            &TxOptions::new(Fee::Flat(fee.as_sat())),
+            network,
+        )?
+        .serialize()
+    }
+
+    /// like `construct_signed`, also reporting the outputs spent
+    pub fn build_signed(
+        &self,
+        utxos: &[Utxo],
+        outputs: &[(&str, Amount)],
+        op_return: Option<&[u8]>,
+        options: &TxOptions,
+        network: Network,
+    ) -> Result<BuiltTx, Error> {
+        let mut built = self.build_unsigned(utxos, outputs, op_return, options, network)?;
+        tx::sign_p2pkh(&mut built.tx, &self.script(), self.mpriv(), &self.pubkey())?;
+        verify::verify_inputs(&built.tx, utxos, network)?;
+        Ok(built)
+    }
+
+    fn build_unsigned(
+        &self,
+        utxos: &[Utxo],
+        outputs: &[(&str, Amount)],
+        op_return: Option<&[u8]>,
+        options: &TxOptions,
+        network: Network,
+    ) -> Result<BuiltTx, Error> {
+        tx::construct_p2pkh_unsigned(&self.pubkey(), utxos, outputs, op_return, options, network)
+    }
+
+    /// works out, without signing, the chain of transactions carrying the
+    /// bork payloads `op_returns`
+    pub fn plan_bork(
+        &self,
+        utxos: &[Utxo],
+        outputs: &[(&str, Amount)],
+        op_returns: &[Vec<u8>],
+        options: &TxOptions,
+        network: Network,
+    ) -> Result<BorkPlan, Error> {
+        ensure!(
+            self.compressed,
+            "borks from uncompressed keys are not attributed to their sender"
+        );
+        let chain = chain::build_chain(
+            utxos,
+            outputs,
+            op_returns,
+            options,
+            |utxos, outputs, op_return| {
+                self.build_unsigned(utxos, outputs, Some(op_return), options, network)
+            },
+        )?;
+        Ok(BorkPlan::new(&chain, op_returns))
+    }
+
+    /// signs the chain `plan_bork` works out, all or nothing
+    pub fn sign_bork(
+        &self,
+        utxos: &[Utxo],
+        outputs: &[(&str, Amount)],
+        op_returns: &[Vec<u8>],
+        options: &TxOptions,
+        network: Network,
+    ) -> Result<Vec<BuiltTx>, Error> {
+        self.plan_bork(utxos, outputs, op_returns, options, network)?;
+        let chain = chain::build_chain(
+            utxos,
+            outputs,
+            op_returns,
+            options,
+            |utxos, outputs, op_return| {
+                self.build_signed(utxos, outputs, Some(op_return), options, network)
+            },
+        )?;
+        verify::verify_borks(&chain, &self.address(network), network)?;
+        Ok(chain)
+    }
+
+    /// re-signs `chain`, which signalled RBF, paying the fee in `options`;
+    /// `utxos` are the outputs its first transaction spends
+    pub fn bump_fee(
+        &self,
+        chain: &[Vec<u8>],
+        utxos: &[Utxo],
+        options: &TxOptions,
+        network: Network,
+    ) -> Result<Vec<BuiltTx>, Error> {
+        bump::replace(
+            chain,
+            utxos,
+            &options.change_script(&self.address(network), network)?,
+            options,
+            network,
+            |utxos, outputs, op_return, options| {
+                self.build_signed(utxos, outputs, op_return, options, network)
+            },
+        )
+    }
+
+    /// spends the change of `parent`, which pays `parent_fee`, so the two
+    /// together pay the fee in `options`
+    pub fn cpfp(
+        &self,
+        parent: &[u8],
+        parent_fee: Amount,
+        options: &TxOptions,
+        network: Network,
+    ) -> Result<BuiltTx, Error> {
+        bump::child_pays_for_parent(
+            parent,
+            parent_fee,
+            &Sender {
+                scripts: &[self.script()],
+                script_sig_len: self.script_sig_len(),
+                address: &self.address(network),
+            },
+            options,
+            network,
+            |utxos, options| self.build_signed(utxos, &[], None, options, network),
+        )
+    }
+
+    pub fn sign_psbt(&self, psbt: &[u8]) -> Result<Vec<u8>, Error> {
+        psbt::sign(psbt, self.mpriv(), &self.pubkey())
+    }
+}
+impl std::fmt::Debug for KeyWallet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyWallet")
+            .field("mpriv", &"[REDACTED]")
+            .field("mpub", &hex::encode(self.pubkey()))
+            .field("compressed", &self.compressed)
+            .field("nonce_counter", &self.nonce_counter)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the key of Bitcoin Core's `rpc_signmessage.py`, as Dogecoin Core's
+    /// `dumpprivkey` gives it
+    const DOGE_WIF: &str = "QVgEyYtwgYEur54AMgzdw8dDiKcf3nun2xG1EPZ4hR7vTvqpqiJ9";
+    const DOGE_WIF_UNCOMPRESSED: &str = "6KjRTrpZnD4c5msZUoDc6vPFfMz1vCJrAxZrSh11qaZP5nMXm2p";
+
+    #[test]
+    fn wif_round_trip() {
+        let key = KeyWallet::from_wif(DOGE_WIF, Network::Dogecoin).unwrap();
+        assert!(key.is_compressed());
+        assert_eq!(
+            hex::encode(key.mpriv().serialize()),
+            "d2b8a0116d641fe7d3036f8464628fb595b480414c13a301b3d4038c811c28b0"
+        );
+        assert_eq!(
+            key.address(Network::Dogecoin),
+            "DDxYysAmdyaDxNmUNKXRE1gMNy5iy7vuMY"
+        );
+        assert_eq!(key.to_wif(Network::Dogecoin), DOGE_WIF);
+
+        let key = KeyWallet::from_wif(DOGE_WIF_UNCOMPRESSED, Network::Dogecoin).unwrap();
+        assert!(!key.is_compressed());
+        assert_eq!(
+            key.address(Network::Dogecoin),
+            "DGvdSBdNRKGAAiVBcFVm3KKJxtHdvAGhe1"
+        );
+        assert_eq!(key.to_wif(Network::Dogecoin), DOGE_WIF_UNCOMPRESSED);
+    }
+
+    #[test]
+    fn wif_bitcoin() {
+        let key = KeyWallet::from_wif(
+            "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ",
+            Network::Bitcoin,
+        )
+        .unwrap();
+        assert_eq!(
+            key.address(Network::Bitcoin),
+            "1GAehh7TsJAHuUAeKZcXf5CnwuGuGgyX2S"
+        );
+        let key = KeyWallet::from_wif(
+            "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn",
+            Network::Bitcoin,
+        )
+        .unwrap();
+        assert_eq!(
+            key.address(Network::Bitcoin),
+            "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH"
+        );
+    }
+
+    #[test]
+    fn wif_wrong_network() {
+        assert!(KeyWallet::from_wif(DOGE_WIF, Network::Bitcoin).is_err());
+        assert!(KeyWallet::from_wif(DOGE_WIF, Network::Litecoin).is_err());
+    }
+}
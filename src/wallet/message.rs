@@ -0,0 +1,149 @@
+use super::pubkey_to_addr;
+use crate::Network;
+use failure::Error;
+use secp256k1::{PublicKey, SecretKey};
+
+pub fn magic(network: Network) -> &'static str {
+    match network {
+        Network::Dogecoin => "Dogecoin Signed Message:\n",
+        Network::Litecoin => "Litecoin Signed Message:\n",
+        Network::Bitcoin => "Bitcoin Signed Message:\n",
+    }
+}
+
+/// double sha256 of the length-prefixed magic and message, as hashed by
+/// `signmessage` in Dogecoin Core and Litecoin Core
+pub fn message_hash(message: &str, network: Network) -> Result<[u8; 32], Error> {
+    use bitcoin::consensus::Encodable;
+    use bitcoin_hashes::{sha256d, Hash};
+
+    let mut data = Vec::new();
+    for s in [magic(network), message].iter() {
+        bitcoin::VarInt(s.len() as u64).consensus_encode(&mut data)?;
+        data.extend_from_slice(s.as_bytes());
+    }
+    Ok(sha256d::Hash::hash(&data).into_inner())
+}
+
+/// base64 compact recoverable signature: header byte (27 + recovery id, plus 4
+/// for compressed keys) followed by r and s
+pub fn sign_message(
+    key: &SecretKey,
+    compressed: bool,
+    message: &str,
+    network: Network,
+) -> Result<String, Error> {
+    let hash = message_hash(message, network)?;
+    let (sig, recid) = secp256k1::sign(&secp256k1::Message::parse(&hash), key);
+    let mut res = vec![27 + recid.serialize() + if compressed { 4 } else { 0 }];
+    res.extend_from_slice(&sig.serialize());
+    Ok(base64::encode(&res))
+}
+
+pub fn verify_message(
+    address: &str,
+    signature: &str,
+    message: &str,
+    network: Network,
+) -> Result<bool, Error> {
+    let sig = base64::decode(signature)?;
+    ensure!(sig.len() == 65, "invalid signature length");
+    ensure!(27 <= sig[0] && sig[0] < 35, "invalid signature header");
+    let recid =
+        secp256k1::RecoveryId::parse((sig[0] - 27) & 3).map_err(|e| format_err!("{:?}", e))?;
+    let compressed = (sig[0] - 27) & 4 != 0;
+    let hash = message_hash(message, network)?;
+    let pubkey: PublicKey = match secp256k1::recover(
+        &secp256k1::Message::parse(&hash),
+        &secp256k1::Signature::parse_slice(&sig[1..]).map_err(|e| format_err!("{:?}", e))?,
+        &recid,
+    ) {
+        Ok(pk) => pk,
+        Err(_) => return Ok(false),
+    };
+    let recovered = if compressed {
+        pubkey_to_addr(&pubkey.serialize_compressed(), network)
+    } else {
+        pubkey_to_addr(&pubkey.serialize(), network)
+    };
+    Ok(recovered == address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &str = "This is just a test message";
+
+    fn key() -> SecretKey {
+        SecretKey::parse_slice(
+            &hex::decode("d2b8a0116d641fe7d3036f8464628fb595b480414c13a301b3d4038c811c28b0")
+                .unwrap(),
+        )
+        .unwrap()
+    }
+
+    /// Bitcoin Core's `rpc_signmessage.py`
+    #[test]
+    fn bitcoin_core() {
+        let sig = "INbVnW4e6PeRmsv2Qgu8NuopvrVjkcxob+sX8OcZG0SALhWybUjzMLPdAsXI46YZGb0KQTRii+wWIQzRpG/U+S0=";
+        assert_eq!(
+            sign_message(&key(), true, MESSAGE, Network::Bitcoin).unwrap(),
+            sig
+        );
+        assert!(verify_message(
+            "19pTScE8LZfwRNasdjXrgFWkVqMRcU99GK",
+            sig,
+            MESSAGE,
+            Network::Bitcoin
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn dogecoin() {
+        let vectors = [
+            (
+                true,
+                "DDxYysAmdyaDxNmUNKXRE1gMNy5iy7vuMY",
+                "IDvq0cVA+JzqV2XKuwO4r30l6t7BoVwT9zJNdjo6ztTkeYpQKGuaEjjsVIlsB4q2J4q3pLeXymKI+ZGuX9xQv+I=",
+            ),
+            (
+                false,
+                "DGvdSBdNRKGAAiVBcFVm3KKJxtHdvAGhe1",
+                "HDvq0cVA+JzqV2XKuwO4r30l6t7BoVwT9zJNdjo6ztTkeYpQKGuaEjjsVIlsB4q2J4q3pLeXymKI+ZGuX9xQv+I=",
+            ),
+        ];
+        for (compressed, address, sig) in vectors.iter() {
+            assert_eq!(
+                sign_message(&key(), *compressed, MESSAGE, Network::Dogecoin).unwrap(),
+                *sig
+            );
+            assert!(verify_message(address, sig, MESSAGE, Network::Dogecoin).unwrap());
+            assert!(!verify_message(address, sig, "another message", Network::Dogecoin).unwrap());
+            assert!(!verify_message(address, sig, MESSAGE, Network::Litecoin).unwrap());
+        }
+        // the header says which form of the key signed
+        assert!(!verify_message(
+            "DGvdSBdNRKGAAiVBcFVm3KKJxtHdvAGhe1",
+            vectors[0].2,
+            MESSAGE,
+            Network::Dogecoin
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn malformed() {
+        let address = "DDxYysAmdyaDxNmUNKXRE1gMNy5iy7vuMY";
+        assert!(verify_message(address, "AAAA", MESSAGE, Network::Dogecoin).is_err());
+        let mut sig = base64::decode(
+            "IDvq0cVA+JzqV2XKuwO4r30l6t7BoVwT9zJNdjo6ztTkeYpQKGuaEjjsVIlsB4q2J4q3pLeXymKI+ZGuX9xQv+I=",
+        )
+        .unwrap();
+        sig[0] = 35;
+        assert!(
+            verify_message(address, &base64::encode(&sig), MESSAGE, Network::Dogecoin).is_err()
+        );
+    }
+}
@@ -1,7 +1,47 @@
+mod amount;
+mod bump;
+mod chain;
 mod child;
 mod consts;
-
+mod discovery;
+mod encrypt;
+mod error;
+mod fee;
+mod key;
+mod message;
+mod multikey;
+mod multisig;
+mod nonce;
+mod path;
+mod psbt;
+mod secret;
+mod select;
+mod shamir;
+mod sweep;
+mod tx;
+mod verify;
+mod watch;
+
+pub use self::amount::Amount;
+pub use self::chain::{BorkPlan, PlannedTx};
 pub use self::child::ChildWallet;
+use self::child::SerializableKey;
+pub use self::discovery::{AccountDiscovery, DEFAULT_GAP_LIMIT};
+pub use self::encrypt::is_encrypted;
+pub use self::error::TxBuildError;
+pub use self::fee::Fee;
+pub(crate) use self::fee::{data_carrier_size, max_relayed_op_return_len};
+pub use self::key::{privkey_to_wif, wif_to_privkey, KeyWallet};
+pub use self::message::verify_message;
+pub use self::multisig::MultisigWallet;
+pub use self::path::{ChildNumber, DerivationPath};
+pub use self::psbt::{combine_psbts, create_psbt, extract_psbt, finalize_psbt};
+pub use self::secret::{Entropy, Secret, Seed};
+pub use self::select::CoinSelection;
+pub use self::shamir::Share;
+pub use self::sweep::sweep;
+pub use self::tx::{BuiltTx, ChangePolicy, Sender, TxOptions, Utxo};
+pub use self::watch::WatchOnlyWallet;
 use crate::Network;
 use base58::ToBase58;
 use failure::Error;
@@ -9,24 +49,40 @@ use pbkdf2::pbkdf2;
 use ripemd160::Digest;
 use ripemd160::Ripemd160;
 use sha2::Sha256;
+use zeroize::Zeroize;
 
 pub type HmacSha512 = hmac::Hmac<sha2::Sha512>;
 
+/// finishes `mac` into a seed, wiping the intermediate output buffer
+fn mac_to_seed(mac: HmacSha512) -> Seed {
+    use hmac::Mac;
+
+    let mut code = mac.result().code();
+    let mut res = Seed::new([0; 64]);
+    res.copy_from_slice(&code);
+    code.as_mut_slice().zeroize();
+    res
+}
+
 #[derive(Clone)]
 pub struct Wallet {
-    entropy: [u8; 16],
+    entropy: Entropy,
     parent: Option<ChildWallet>,
 }
 impl Wallet {
     pub fn new() -> Self {
         use rand::RngCore;
 
-        let mut res: [u8; 16] = [0; 16];
-        rand::thread_rng().fill_bytes(&mut res);
-        Self::from_entropy(res)
+        let mut res = Entropy::new([0; 16]);
+        rand::thread_rng().fill_bytes(&mut *res);
+        Self::from_secret(res)
     }
 
     pub fn from_entropy(entropy: [u8; 16]) -> Self {
+        Self::from_secret(Entropy::new(entropy))
+    }
+
+    fn from_secret(entropy: Entropy) -> Self {
         let mut res = Wallet {
             entropy,
             parent: None,
@@ -40,7 +96,7 @@ impl Wallet {
     }
 
     pub fn entropy(&self) -> &[u8] {
-        &self.entropy
+        &*self.entropy
     }
 
     fn sha256sum(&self) -> u8 {
@@ -83,7 +139,7 @@ impl Wallet {
     }
 
     fn from_idxs(idxs: [u16; 12]) -> Result<Self, failure::Error> {
-        let mut entropy: [u8; 16] = [0; 16];
+        let mut entropy = Entropy::new([0; 16]);
         let mut idx = 0;
         let mut overflow: u16 = 0;
         let mut overflow_bits: u32 = 0;
@@ -100,7 +156,7 @@ impl Wallet {
                 overflow = overflow & mask_16!(overflow_bits);
             }
         }
-        let res = Self::from_entropy(entropy);
+        let res = Self::from_secret(entropy);
         ensure!(
             res.sha256sum() == overflow as u8,
             "checksum verification failed"
@@ -108,6 +164,22 @@ impl Wallet {
         Ok(res)
     }
 
+    /// splits the entropy into shares, `groups` holding the member threshold
+    /// and member count of each group
+    pub fn split(
+        &self,
+        group_threshold: u8,
+        groups: &[(u8, u8)],
+    ) -> Result<Vec<Vec<Share>>, Error> {
+        shamir::split(self.entropy(), group_threshold, groups)
+    }
+
+    pub fn combine(shares: &[Share]) -> Result<Self, Error> {
+        let mut entropy = Entropy::new([0; 16]);
+        entropy.copy_from_slice(&shamir::combine(shares)?);
+        Ok(Self::from_secret(entropy))
+    }
+
     pub fn words(&self) -> [&'static str; 12] {
         let mut res = [""; 12];
         for (word, idx) in res.iter_mut().zip(self.idxs().iter()) {
@@ -137,21 +209,15 @@ impl Wallet {
         match self.parent {
             Some(_) => (),
             None => {
-                let mut seed: [u8; 64] = [0; 64];
+                let mut seed = Seed::new([0; 64]);
+                let words = Secret::new(self.words().join(" "));
 
-                pbkdf2::<HmacSha512>(
-                    self.words().join(" ").as_bytes(),
-                    b"mnemonic",
-                    2048,
-                    &mut seed,
-                );
+                pbkdf2::<HmacSha512>(words.as_bytes(), b"mnemonic", 2048, &mut *seed);
 
                 use hmac::Mac;
                 let mut mac = HmacSha512::new_varkey(b"Bitcoin seed").unwrap();
-                mac.input(&seed);
-                let mut l: [u8; 64] = [0; 64];
-                l.clone_from_slice(mac.result().code().as_slice());
-                self.parent = Some(ChildWallet::new(l));
+                mac.input(&*seed);
+                self.parent = Some(ChildWallet::from_seed(mac_to_seed(mac)));
             }
         }
     }
@@ -164,18 +230,111 @@ impl Wallet {
         self.parent.as_mut().expect("wallet uninitialized")
     }
 
-    fn serializable(&self) -> Result<SerializableWallet, Error> {
-        Ok(SerializableWallet {
-            entropy: self.entropy.to_vec(),
-            parent: match self.parent {
-                Some(ref a) => Some(ByteVec(a.as_bytes()?)),
-                None => None,
+    pub fn derive(&mut self, path: &DerivationPath) -> Result<&mut ChildWallet, Error> {
+        self.parent_mut().derive(path)
+    }
+
+    /// keys of the child at `sender` and then those at `paths`
+    fn keys(
+        &mut self,
+        sender: &DerivationPath,
+        paths: &[DerivationPath],
+    ) -> Result<Vec<KeyWallet>, Error> {
+        std::iter::once(sender)
+            .chain(paths)
+            .map(|path| Ok(self.derive(path)?.to_key_wallet()))
+            .collect()
+    }
+
+    /// Builds a transaction spending outputs of the children at `sender` and
+    /// `paths`, each input signed by the child it pays to. `sender` signs
+    /// input 0, so is who a bork in `op_return` is from, and gets the change.
+    pub fn build_signed(
+        &mut self,
+        sender: &DerivationPath,
+        paths: &[DerivationPath],
+        utxos: &[Utxo],
+        outputs: &[(&str, Amount)],
+        op_return: Option<&[u8]>,
+        options: &TxOptions,
+        network: Network,
+    ) -> Result<BuiltTx, Error> {
+        let keys = self.keys(sender, paths)?;
+        multikey::build_signed(&keys, utxos, outputs, op_return, options, network)
+    }
+
+    /// like `KeyWallet::sign_bork` for the child at `sender`, also spending
+    /// outputs of the children at `paths`
+    pub fn sign_bork(
+        &mut self,
+        sender: &DerivationPath,
+        paths: &[DerivationPath],
+        utxos: &[Utxo],
+        outputs: &[(&str, Amount)],
+        op_returns: &[Vec<u8>],
+        options: &TxOptions,
+        network: Network,
+    ) -> Result<Vec<BuiltTx>, Error> {
+        let keys = self.keys(sender, paths)?;
+        let chain = chain::build_chain(
+            utxos,
+            outputs,
+            op_returns,
+            options,
+            |utxos, outputs, op_return| {
+                multikey::build_signed(&keys, utxos, outputs, Some(op_return), options, network)
             },
-        })
+        )?;
+        verify::verify_borks(&chain, &keys[0].address(network), network)?;
+        Ok(chain)
+    }
+
+    /// spends everything the children at `paths` can from `utxos` to
+    /// `address`, e.g. when moving off an old derivation path
+    pub fn sweep(
+        &mut self,
+        paths: &[DerivationPath],
+        utxos: &[Utxo],
+        address: &str,
+        options: &TxOptions,
+        network: Network,
+    ) -> Result<BuiltTx, Error> {
+        let keys = paths
+            .iter()
+            .map(|path| Ok(self.derive(path)?.to_key_wallet()))
+            .collect::<Result<Vec<_>, Error>>()?;
+        sweep::sweep(&keys, utxos, address, options, network)
+    }
+
+    pub fn discover<F: FnMut(&str) -> bool>(
+        &self,
+        network: Network,
+        gap_limit: u32,
+        is_used: F,
+    ) -> Result<Vec<AccountDiscovery>, Error> {
+        discovery::discover(self.parent(), network, gap_limit, is_used)
+    }
+
+    fn serializable(&self) -> SerializableWallet {
+        SerializableWallet {
+            entropy: self.entropy.to_vec(),
+            keys: self.parent().keys(),
+        }
     }
 
     fn from_serializable(w: SerializableWallet) -> Result<Self, Error> {
-        let mut entropy: [u8; 16] = [0; 16];
+        ensure!(w.entropy.len() == 16, "invalid entropy length");
+        let mut entropy = Entropy::new([0; 16]);
+        entropy.clone_from_slice(&w.entropy);
+
+        let mut res = Self::from_secret(entropy);
+        res.parent_mut().restore_keys(&w.keys)?;
+        Ok(res)
+    }
+
+    fn from_legacy(w: LegacyWallet) -> Result<Self, Error> {
+        ensure!(w.entropy.len() == 16, "invalid entropy length");
+        let mut entropy = Entropy::new([0; 16]);
         entropy.clone_from_slice(&w.entropy);
 
         let parent = match w.parent {
@@ -183,7 +342,9 @@ impl Wallet {
             None => None,
         };
 
-        Ok(Wallet { entropy, parent })
+        let mut res = Wallet { entropy, parent };
+        res.init();
+        Ok(res)
     }
 
     pub fn check_ser(&self) -> Result<(), Error> {
@@ -195,49 +356,142 @@ impl Wallet {
     }
 
     pub fn as_bytes(&self) -> Result<Vec<u8>, Error> {
-        Ok(serde_cbor::to_vec(&self.serializable()?)?)
+        Ok(serde_cbor::ser::to_vec_packed(&self.serializable())?)
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
-        let w: SerializableWallet = serde_cbor::from_slice(bytes)?;
-        Self::from_serializable(w)
+        if encrypt::is_encrypted(bytes) {
+            bail!("wallet is encrypted");
+        }
+        if is_legacy(bytes) {
+            Self::from_legacy(serde_cbor::from_slice(bytes)?)
+        } else {
+            Self::from_serializable(serde_cbor::from_slice(bytes)?)
+        }
+    }
+
+    pub fn as_encrypted_bytes(&self, password: &str) -> Result<Vec<u8>, Error> {
+        encrypt::encrypt(&Secret::new(self.as_bytes()?), password)
+    }
+
+    pub fn from_encrypted_bytes(bytes: &[u8], password: &str) -> Result<Self, Error> {
+        Self::from_bytes(&Secret::new(encrypt::decrypt(bytes, password)?))
+    }
+}
+impl std::fmt::Debug for Wallet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Wallet")
+            .field("entropy", &self.entropy)
+            .field("parent", &self.parent)
+            .finish()
     }
 }
 
 #[derive(Deserialize, Serialize)]
 struct ByteVec(#[serde(with = "serde_bytes")] pub Vec<u8>);
+impl Drop for ByteVec {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
 
+/// only the entropy and the paths of loaded keys are stored; every key is
+/// re-derived on load
 #[derive(Deserialize, Serialize)]
 pub struct SerializableWallet {
+    #[serde(with = "serde_bytes")]
+    entropy: Vec<u8>,
+    keys: Vec<SerializableKey>,
+}
+impl Drop for SerializableWallet {
+    fn drop(&mut self) {
+        self.entropy.zeroize();
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct LegacyWallet {
     #[serde(with = "serde_bytes")]
     entropy: Vec<u8>,
     parent: Option<ByteVec>,
 }
+impl Drop for LegacyWallet {
+    fn drop(&mut self) {
+        self.entropy.zeroize();
+    }
+}
 
-pub fn addr_to_script(addr: &str, network: Network) -> Result<bitcoin::Script, Error> {
-    use bitcoin::util::base58;
+/// The legacy formats were written with named fields, the current ones packed
+/// with numbered fields: a legacy buffer is a map whose first key is a string.
+fn is_legacy(bytes: &[u8]) -> bool {
+    let first_key = match bytes.first() {
+        Some(b) if b >> 5 == 5 => match b & 0x1F {
+            0..=23 => 1,
+            24 => 2,
+            25 => 3,
+            26 => 5,
+            27 => 9,
+            _ => return false,
+        },
+        _ => return false,
+    };
+    bytes.get(first_key).map(|b| b >> 5 == 3).unwrap_or(false)
+}
 
-    let mut addr_bytes = base58::from_check(addr)?;
-    let v = addr_bytes.remove(0);
-    if v != match network {
+pub fn p2pkh_version(network: Network) -> u8 {
+    match network {
         Network::Dogecoin => 0x1E,
         Network::Litecoin => 0x30,
         Network::Bitcoin => 0x00,
-    } {
-        bail!("address version {:x} is not p2pkh", v);
     }
+}
+
+pub fn p2sh_version(network: Network) -> u8 {
+    match network {
+        Network::Dogecoin => 0x16,
+        Network::Litecoin => 0x32,
+        Network::Bitcoin => 0x05,
+    }
+}
 
-    let mut s: Vec<u8> = vec![0x76, 0xA9, addr_bytes.len() as u8];
-    s.extend(addr_bytes);
-    s.push(0x88);
-    s.push(0xAC);
+pub fn addr_to_script(addr: &str, network: Network) -> Result<bitcoin::Script, Error> {
+    use bitcoin::util::base58;
+
+    let invalid = |reason: String| TxBuildError::InvalidAddress {
+        address: addr.to_owned(),
+        network,
+        reason,
+    };
+    let mut addr_bytes = base58::from_check(addr).map_err(|e| invalid(e.to_string()))?;
+    if addr_bytes.len() != 21 {
+        return Err(invalid("invalid address length".to_owned()).into());
+    }
+    let v = addr_bytes.remove(0);
+    let s = if v == p2pkh_version(network) {
+        let mut s: Vec<u8> = vec![0x76, 0xA9, addr_bytes.len() as u8];
+        s.extend(addr_bytes);
+        s.push(0x88);
+        s.push(0xAC);
+        s
+    } else if v == p2sh_version(network) || (network == Network::Litecoin && v == 0x05) {
+        // litecoin addresses with bitcoin's p2sh version are still in use
+        let mut s: Vec<u8> = vec![0xA9, addr_bytes.len() as u8];
+        s.extend(addr_bytes);
+        s.push(0x87);
+        s
+    } else {
+        return Err(invalid(format!("address version {:x} is not p2pkh or p2sh", v)).into());
+    };
 
     Ok(bitcoin::Script::from(s))
 }
 
 pub fn script_to_addr(script: &bitcoin::Script, network: Network) -> Result<String, Error> {
+    if script.is_p2sh() {
+        return Ok(script_hash_to_addr(&script.as_bytes()[2..22], network));
+    }
     if !script.is_p2pkh() {
-        bail!("not p2pkh");
+        bail!("not p2pkh or p2sh");
     }
     let mut script = script.iter(true);
     script.next();
@@ -255,15 +509,9 @@ pub fn is_p2pkh(first: u8) -> bool {
     first == 0x00 || first == 0x1E || first == 0x30
 }
 
-pub fn pubkey_hash_to_addr(pkh: &[u8], network: Network) -> String {
-    let version_byte: u8 = match network {
-        Network::Dogecoin => 0x1E,
-        Network::Litecoin => 0x30,
-        Network::Bitcoin => 0x00,
-    };
-
+fn hash_to_addr(version_byte: u8, hash: &[u8]) -> String {
     let mut addr_bytes: Vec<u8> = vec![version_byte];
-    addr_bytes.extend(pkh);
+    addr_bytes.extend(hash);
 
     let mut hasher = Sha256::new();
     hasher.input(&addr_bytes);
@@ -277,14 +525,26 @@ pub fn pubkey_hash_to_addr(pkh: &[u8], network: Network) -> String {
     ToBase58::to_base58(addr_bytes.as_slice())
 }
 
-pub fn pubkey_to_addr(pk: &[u8], network: Network) -> String {
+pub fn pubkey_hash_to_addr(pkh: &[u8], network: Network) -> String {
+    hash_to_addr(p2pkh_version(network), pkh)
+}
+
+pub fn script_hash_to_addr(sh: &[u8], network: Network) -> String {
+    hash_to_addr(p2sh_version(network), sh)
+}
+
+pub fn hash160(data: &[u8]) -> Vec<u8> {
     let mut hasher = Sha256::new();
-    hasher.input(pk);
+    hasher.input(data);
     let sha_bytes = hasher.result();
 
     let mut hasher = Ripemd160::new();
     hasher.input(&sha_bytes);
     let ripe_bytes = hasher.result();
 
-    pubkey_hash_to_addr(ripe_bytes.as_slice(), network)
+    ripe_bytes.to_vec()
+}
+
+pub fn pubkey_to_addr(pk: &[u8], network: Network) -> String {
+    pubkey_hash_to_addr(&hash160(pk), network)
 }
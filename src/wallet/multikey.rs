@@ -0,0 +1,73 @@
+//! Spending the outputs of several keys in one transaction, e.g. funds spread
+//! over a wallet's addresses. The first key is the sender: it signs input 0,
+//! which makes it the author of a bork, and gets the change.
+
+use super::amount::Amount;
+use super::fee;
+use super::tx::{self, BuiltTx, Sender, TxOptions, Utxo};
+use super::verify;
+use super::KeyWallet;
+use crate::Network;
+use bitcoin::Transaction;
+use failure::Error;
+
+/// signs each input of `tx` with the one of `keys` the output it spends, found
+/// in `utxos`, pays to
+pub fn sign_with_keys(
+    tx: &mut Transaction,
+    keys: &[KeyWallet],
+    utxos: &[Utxo],
+) -> Result<(), Error> {
+    let scripts = keys.iter().map(|k| k.script()).collect::<Vec<_>>();
+    let mut own = vec![Vec::new(); keys.len()];
+    for (i, input) in tx.input.iter().enumerate() {
+        let script = &utxos
+            .iter()
+            .find(|u| u.outpoint == input.previous_output)
+            .ok_or(format_err!("input {} spends an unknown output", i))?
+            .output
+            .script_pubkey;
+        let key = scripts
+            .iter()
+            .position(|s| s == script)
+            .ok_or(format_err!("no key to sign input {} with", i))?;
+        own[key].push(i);
+    }
+    for ((key, script), inputs) in keys.iter().zip(&scripts).zip(own) {
+        tx::sign_p2pkh_inputs(tx, &inputs, script, key.mpriv(), &key.pubkey())?;
+    }
+    Ok(())
+}
+
+/// like `KeyWallet::build_signed`, spending outputs of any of `keys` with the
+/// first as the sender
+pub fn build_signed(
+    keys: &[KeyWallet],
+    utxos: &[Utxo],
+    outputs: &[(&str, Amount)],
+    op_return: Option<&[u8]>,
+    options: &TxOptions,
+    network: Network,
+) -> Result<BuiltTx, Error> {
+    let sender = keys.first().ok_or(format_err!("no keys to sign with"))?;
+    let script_sig_len = keys
+        .iter()
+        .map(|k| fee::p2pkh_script_sig_len(k.pubkey().len()))
+        .max()
+        .unwrap_or(0);
+    let mut built = tx::construct_unsigned(
+        &Sender {
+            scripts: &keys.iter().map(|k| k.script()).collect::<Vec<_>>(),
+            script_sig_len,
+            address: &sender.address(network),
+        },
+        utxos,
+        outputs,
+        op_return,
+        options,
+        network,
+    )?;
+    sign_with_keys(&mut built.tx, keys, utxos)?;
+    verify::verify_inputs(&built.tx, utxos, network)?;
+    Ok(built)
+}
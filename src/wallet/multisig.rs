@@ -0,0 +1,346 @@
+use super::amount::Amount;
+use super::fee::{self, Fee};
+use super::hash160;
+use super::nonce;
+use super::script_hash_to_addr;
+use super::tx::{self, BuiltTx, Sender, TxOptions, Utxo};
+use super::verify;
+use super::ChildWallet;
+use crate::Network;
+use bitcoin::{Script, Transaction};
+use failure::Error;
+use secp256k1::PublicKey;
+
+/// An m-of-n P2SH multisig account shared by several cosigners. Keys are
+/// sorted as in BIP67, so every cosigner derives the same address no matter
+/// in which order the public keys were exchanged.
+#[derive(Clone, Debug)]
+pub struct MultisigWallet {
+    threshold: usize,
+    pubkeys: Vec<PublicKey>,
+    redeem_script: Script,
+    /// shared by the cosigners, as borks are all from the account's address
+    nonce_counter: u32,
+}
+impl MultisigWallet {
+    pub fn new(threshold: usize, mut pubkeys: Vec<PublicKey>) -> Result<Self, Error> {
+        use bitcoin::blockdata::opcodes::all::OP_CHECKMULTISIG;
+        use bitcoin::blockdata::script::Builder;
+
+        ensure!(
+            0 < threshold && threshold <= pubkeys.len() && pubkeys.len() <= 15,
+            "invalid threshold {} of {}",
+            threshold,
+            pubkeys.len()
+        );
+        pubkeys.sort_by_key(|k| k.serialize_compressed().to_vec());
+        pubkeys.dedup();
+        ensure!(threshold <= pubkeys.len(), "duplicate cosigner public keys");
+        let redeem_script = pubkeys
+            .iter()
+            .fold(Builder::new().push_int(threshold as i64), |b, k| {
+                b.push_slice(&k.serialize_compressed())
+            })
+            .push_int(pubkeys.len() as i64)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script();
+        Ok(MultisigWallet {
+            threshold,
+            pubkeys,
+            redeem_script,
+            nonce_counter: 0,
+        })
+    }
+
+    pub fn from_public_keys(threshold: usize, pubkeys: &[Vec<u8>]) -> Result<Self, Error> {
+        Self::new(
+            threshold,
+            pubkeys
+                .iter()
+                .map(|k| PublicKey::parse_slice(k, None).map_err(|e| format_err!("{:?}", e)))
+                .collect::<Result<Vec<_>, Error>>()?,
+        )
+    }
+
+    pub fn from_child_wallets(threshold: usize, cosigners: &[&ChildWallet]) -> Result<Self, Error> {
+        Self::new(
+            threshold,
+            cosigners.iter().map(|c| c.mpub().clone()).collect(),
+        )
+    }
+
+    /// parses an `OP_m <pubkey>... OP_n OP_CHECKMULTISIG` redeem script,
+    /// keeping its key order even if it is not sorted
+    pub fn from_redeem_script(script: &[u8]) -> Result<Self, Error> {
+        let op_num = |b: Option<&u8>| match b {
+            Some(b) if 0x51 <= *b && *b <= 0x60 => Ok((b - 0x50) as usize),
+            _ => Err(format_err!("invalid multisig redeem script")),
+        };
+        let threshold = op_num(script.first())?;
+        let mut pubkeys = Vec::new();
+        let mut i = 1;
+        while let Some(len) = script.get(i).filter(|l| **l == 33 || **l == 65) {
+            let key = script
+                .get(i + 1..i + 1 + *len as usize)
+                .ok_or(format_err!("unexpected end of input"))?;
+            pubkeys.push(PublicKey::parse_slice(key, None).map_err(|e| format_err!("{:?}", e))?);
+            i += 1 + *len as usize;
+        }
+        ensure!(
+            op_num(script.get(i))? == pubkeys.len()
+                && script.get(i + 1) == Some(&0xAE)
+                && script.len() == i + 2
+                && threshold <= pubkeys.len(),
+            "invalid multisig redeem script"
+        );
+        Ok(MultisigWallet {
+            threshold,
+            pubkeys,
+            redeem_script: Script::from(script.to_vec()),
+            nonce_counter: 0,
+        })
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    pub fn pubkeys(&self) -> &[PublicKey] {
+        &self.pubkeys
+    }
+
+    pub fn redeem_script(&self) -> &Script {
+        &self.redeem_script
+    }
+
+    pub fn script_hash(&self) -> Vec<u8> {
+        hash160(self.redeem_script.as_bytes())
+    }
+
+    pub fn address(&self, network: Network) -> String {
+        script_hash_to_addr(&self.script_hash(), network)
+    }
+
+    pub fn script(&self) -> Script {
+        self.redeem_script.to_p2sh()
+    }
+
+    pub fn nonce(&mut self) -> u8 {
+        let res = self.next_nonce();
+        self.nonce_counter = self.nonce_counter.wrapping_add(1);
+        res
+    }
+
+    /// the nonce `nonce` returns next, without using it up
+    pub fn next_nonce(&self) -> u8 {
+        nonce::derive(
+            nonce::script_offset(self.redeem_script.as_bytes()),
+            self.nonce_counter,
+        )
+    }
+
+    pub fn nonce_counter(&self) -> u32 {
+        self.nonce_counter
+    }
+
+    pub fn sync_nonce(&mut self, observed: &[u8]) {
+        self.nonce_counter = nonce::sync_counter(
+            nonce::script_offset(self.redeem_script.as_bytes()),
+            self.nonce_counter,
+            observed,
+        );
+    }
+
+    pub fn as_bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(serde_cbor::to_vec(&SerializableMultisigWallet {
+            redeem_script: self.redeem_script.to_bytes(),
+            nonce_counter: self.nonce_counter,
+        })?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let w: SerializableMultisigWallet = serde_cbor::from_slice(bytes)?;
+        Ok(MultisigWallet {
+            nonce_counter: w.nonce_counter,
+            ..Self::from_redeem_script(&w.redeem_script)?
+        })
+    }
+
+    pub fn script_sig_len(&self) -> usize {
+        fee::multisig_script_sig_len(self.threshold, self.redeem_script.len())
+    }
+
+    /// builds a transaction spending this account's outputs with every
+    /// scriptSig holding just the redeem script, ready for cosigners to sign
+    pub fn construct_unsigned(
+        &self,
+        inputs: &[Vec<u8>],
+        outputs: &[(&str, Amount)],
+        fee: Amount,
+        op_return: Option<&[u8]>,
+        network: Network,
+    ) -> Result<Vec<u8>, Error> {
+        self.build_unsigned(
+            &Utxo::from_txs(inputs, &self.script())?,
+            outputs,
+            op_return,
+            &TxOptions::new(Fee::Flat(fee.as_sat())),
+            network,
+        )?
+        .serialize()
+    }
+
+    /// like `construct_unsigned`, also reporting the outputs spent
+    pub fn build_unsigned(
+        &self,
+        utxos: &[Utxo],
+        outputs: &[(&str, Amount)],
+        op_return: Option<&[u8]>,
+        options: &TxOptions,
+        network: Network,
+    ) -> Result<BuiltTx, Error> {
+        use bitcoin::blockdata::script::Builder;
+
+        let mut built = tx::construct_unsigned(
+            &Sender {
+                scripts: &[self.script()],
+                script_sig_len: self.script_sig_len(),
+                address: &self.address(network),
+            },
+            utxos,
+            outputs,
+            op_return,
+            options,
+            network,
+        )?;
+        for vin in built.tx.input.iter_mut() {
+            vin.script_sig = Builder::new()
+                .push_slice(&[])
+                .push_slice(self.redeem_script.as_bytes())
+                .into_script();
+        }
+        Ok(built)
+    }
+
+    /// builds the transaction and adds the first cosigner's signature
+    pub fn construct_signed(
+        &self,
+        signer: &ChildWallet,
+        inputs: &[Vec<u8>],
+        outputs: &[(&str, Amount)],
+        fee: Amount,
+        op_return: Option<&[u8]>,
+        network: Network,
+    ) -> Result<Vec<u8>, Error> {
+        self.build_signed(
+            signer,
+            &Utxo::from_txs(inputs, &self.script())?,
+            outputs,
+            op_return,
+            &TxOptions::new(Fee::Flat(fee.as_sat())),
+            network,
+        )?
+        .serialize()
+    }
+
+    /// like `construct_signed`, also reporting the outputs spent
+    pub fn build_signed(
+        &self,
+        signer: &ChildWallet,
+        utxos: &[Utxo],
+        outputs: &[(&str, Amount)],
+        op_return: Option<&[u8]>,
+        options: &TxOptions,
+        network: Network,
+    ) -> Result<BuiltTx, Error> {
+        let mut built = self.build_unsigned(utxos, outputs, op_return, options, network)?;
+        tx::sign_p2sh_multisig(
+            &mut built.tx,
+            &self.redeem_script,
+            &self.pubkeys,
+            self.threshold,
+            signer.mpriv(),
+        )?;
+        self.verify_signed(&built.tx, signer)?;
+        Ok(built)
+    }
+
+    /// adds `signer`'s signature to a partially signed transaction
+    pub fn sign(&self, signer: &ChildWallet, tx: &[u8]) -> Result<Vec<u8>, Error> {
+        use bitcoin::consensus::Decodable;
+
+        let mut tx = Transaction::consensus_decode(&mut std::io::Cursor::new(tx))?;
+        tx::sign_p2sh_multisig(
+            &mut tx,
+            &self.redeem_script,
+            &self.pubkeys,
+            self.threshold,
+            signer.mpriv(),
+        )?;
+        self.verify_signed(&tx, signer)?;
+        tx::serialize(&tx)
+    }
+
+    /// checks every input of `tx` carries a valid signature by `signer` and,
+    /// once fully signed, verifies against `script` the way nodes will
+    fn verify_signed(&self, tx: &Transaction, signer: &ChildWallet) -> Result<(), Error> {
+        let position = self
+            .pubkeys
+            .iter()
+            .position(|k| k == signer.mpub())
+            .ok_or(format_err!("key is not a cosigner"))?;
+        let mut complete = true;
+        for i in 0..tx.input.len() {
+            let sigs = tx::multisig_signatures(&tx.input[i].script_sig);
+            let slots =
+                tx::match_multisig_signatures(tx, i, &self.redeem_script, &self.pubkeys, &sigs)
+                    .ok_or(format_err!("invalid signature in input {}", i))?;
+            ensure!(
+                slots[position].is_some(),
+                "input {} is missing the signature just added",
+                i
+            );
+            complete &= slots.iter().flatten().count() >= self.threshold;
+        }
+        if complete {
+            // every input spends `script`, and legacy sighashes need no
+            // amounts; the address only encodes it, so any network does
+            for i in 0..tx.input.len() {
+                verify::verify_input(tx, i, &self.script(), Network::Bitcoin)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// valid signatures on `input`, or `None` if any signature is invalid
+    pub fn input_signature_count(&self, tx: &Transaction, input: usize) -> Option<usize> {
+        let sigs = tx::multisig_signatures(&tx.input.get(input)?.script_sig);
+        tx::match_multisig_signatures(tx, input, &self.redeem_script, &self.pubkeys, &sigs)
+            .map(|s| s.iter().flatten().count())
+    }
+
+    /// number of valid signatures on the input with the fewest
+    pub fn signature_count(&self, tx: &[u8]) -> Result<usize, Error> {
+        use bitcoin::consensus::Decodable;
+
+        let tx = Transaction::consensus_decode(&mut std::io::Cursor::new(tx))?;
+        (0..tx.input.len())
+            .map(|i| {
+                self.input_signature_count(&tx, i)
+                    .ok_or(format_err!("invalid signature in input {}", i))
+            })
+            .collect::<Result<Vec<_>, Error>>()
+            .map(|c| c.into_iter().min().unwrap_or(0))
+    }
+
+    pub fn is_complete(&self, tx: &[u8]) -> Result<bool, Error> {
+        Ok(self.signature_count(tx)? >= self.threshold)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SerializableMultisigWallet {
+    #[serde(with = "serde_bytes")]
+    redeem_script: Vec<u8>,
+    nonce_counter: u32,
+}
@@ -0,0 +1,52 @@
+use super::Secret;
+use hmac::Mac;
+use secp256k1::SecretKey;
+
+pub type HmacSha256 = hmac::Hmac<sha2::Sha256>;
+
+fn offset(id: &[u8]) -> u8 {
+    let mut mac = HmacSha256::new_varkey(id).unwrap();
+    mac.input(b"borker nonce");
+    mac.result().code()[0]
+}
+
+/// where the nonce sequence of borks signed by `key` starts
+pub fn key_offset(key: &SecretKey) -> u8 {
+    offset(&Secret::new(key.serialize())[..])
+}
+
+/// where the nonce sequence of a multisig account starts, the same for every
+/// cosigner as they all know its redeem script
+pub fn script_offset(redeem_script: &[u8]) -> u8 {
+    offset(redeem_script)
+}
+
+/// Bork nonces count up from an offset derived from the sender instead of
+/// being drawn at random, so the same wallet restored on another device walks
+/// the same sequence and can catch up from the borks it already sees on chain.
+/// Any 256 consecutive borks get distinct nonces.
+pub fn derive(offset: u8, counter: u32) -> u8 {
+    offset.wrapping_add(counter as u8)
+}
+
+/// the lowest counter `derive` maps to `nonce`
+pub fn counter_of(offset: u8, nonce: u8) -> u32 {
+    nonce.wrapping_sub(offset) as u32
+}
+
+/// Finds the counter past `observed` (the nonces of the sender's borks in chain
+/// order, excluding extensions). The last nonce gives the counter modulo 256;
+/// as every bork before it used up a counter, it is the first such counter no
+/// lower than their number. Never moves the counter backwards.
+pub fn sync_counter(offset: u8, counter: u32, observed: &[u8]) -> u32 {
+    let last = match observed.last() {
+        Some(nonce) => counter_of(offset, *nonce),
+        None => return counter,
+    };
+    let before = observed.len() as u32 - 1;
+    let mut c = (before & !0xFF) | last;
+    if c < before {
+        c += 0x100;
+    }
+    c.saturating_add(1).max(counter)
+}
@@ -0,0 +1,165 @@
+use failure::Error;
+use std::fmt;
+use std::str::FromStr;
+
+pub const HARDENED_OFFSET: u32 = 1 << 31;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ChildNumber {
+    Normal(u32),
+    Hardened(u32),
+}
+impl ChildNumber {
+    pub fn new(index: u32, hardened: bool) -> Result<Self, Error> {
+        ensure!(
+            index < HARDENED_OFFSET,
+            "child index {} out of range",
+            index
+        );
+        Ok(if hardened {
+            ChildNumber::Hardened(index)
+        } else {
+            ChildNumber::Normal(index)
+        })
+    }
+
+    pub fn index(&self) -> u32 {
+        match self {
+            ChildNumber::Normal(i) | ChildNumber::Hardened(i) => *i,
+        }
+    }
+
+    /// the BIP32 index, with the high bit set for hardened children
+    pub fn to_u32(&self) -> u32 {
+        match self {
+            ChildNumber::Normal(i) => *i,
+            ChildNumber::Hardened(i) => HARDENED_OFFSET + i,
+        }
+    }
+
+    pub fn is_hardened(&self) -> bool {
+        match self {
+            ChildNumber::Normal(_) => false,
+            ChildNumber::Hardened(_) => true,
+        }
+    }
+}
+impl fmt::Display for ChildNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChildNumber::Normal(i) => write!(f, "{}", i),
+            ChildNumber::Hardened(i) => write!(f, "{}'", i),
+        }
+    }
+}
+impl FromStr for ChildNumber {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (idx, hardened) = if s.ends_with('\'') || s.ends_with('h') || s.ends_with('H') {
+            (&s[..s.len() - 1], true)
+        } else {
+            (s, false)
+        };
+        // u32 parsing would also take a leading '+'
+        ensure!(
+            !idx.is_empty() && idx.bytes().all(|b| b.is_ascii_digit()),
+            "invalid child index: {}",
+            s
+        );
+        let index = idx
+            .parse::<u32>()
+            .map_err(|_| format_err!("invalid child index: {}", s))?;
+        ChildNumber::new(index, hardened)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DerivationPath(Vec<ChildNumber>);
+impl DerivationPath {
+    pub fn master() -> Self {
+        DerivationPath(Vec::new())
+    }
+
+    /// m/44'/coin'/account'/chain/index
+    pub fn bip44(coin_type: u32, account: u32, chain: u32, index: u32) -> Result<Self, Error> {
+        Ok(DerivationPath(vec![
+            ChildNumber::new(44, true)?,
+            ChildNumber::new(coin_type, true)?,
+            ChildNumber::new(account, true)?,
+            ChildNumber::new(chain, false)?,
+            ChildNumber::new(index, false)?,
+        ]))
+    }
+
+    pub fn child(&self, child: ChildNumber) -> Self {
+        let mut res = self.clone();
+        res.0.push(child);
+        res
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, ChildNumber> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+impl From<Vec<ChildNumber>> for DerivationPath {
+    fn from(v: Vec<ChildNumber>) -> Self {
+        DerivationPath(v)
+    }
+}
+impl AsRef<[ChildNumber]> for DerivationPath {
+    fn as_ref(&self) -> &[ChildNumber] {
+        &self.0
+    }
+}
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "m")?;
+        for c in self.iter() {
+            write!(f, "/{}", c)?;
+        }
+        Ok(())
+    }
+}
+impl FromStr for DerivationPath {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split('/');
+        // 'M' is for public derivation, which these paths are not
+        match parts.next() {
+            Some("m") => (),
+            _ => bail!("derivation path must start with 'm'"),
+        }
+        Ok(DerivationPath(
+            parts
+                .map(ChildNumber::from_str)
+                .collect::<Result<Vec<_>, Error>>()?,
+        ))
+    }
+}
+impl serde::Serialize for DerivationPath {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+impl<'de> serde::Deserialize<'de> for DerivationPath {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,416 @@
+//! BIP174 partially signed transactions, so a transaction prepared by a
+//! watch-only wallet (e.g. on a server) can be signed by a key held elsewhere.
+//! Only p2pkh and p2sh multisig inputs are supported, matching what the
+//! wallets can spend.
+
+use super::amount::Amount;
+use super::hash160;
+use super::tx::{self, Sender, TxOptions, Utxo};
+use super::MultisigWallet;
+use crate::Network;
+use bitcoin::consensus::encode;
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{Script, SigHashType, Transaction};
+use failure::Error;
+use secp256k1::SecretKey;
+use std::collections::HashMap;
+
+fn decode(psbt: &[u8]) -> Result<PartiallySignedTransaction, Error> {
+    Ok(encode::deserialize(psbt)?)
+}
+
+fn psbt_pubkey(pubkey: &[u8]) -> Result<bitcoin::PublicKey, Error> {
+    Ok(bitcoin::PublicKey::from_slice(pubkey)?)
+}
+
+fn pubkey_bytes(pubkey: &bitcoin::PublicKey) -> Vec<u8> {
+    let mut res = Vec::new();
+    pubkey.write_into(&mut res);
+    res
+}
+
+/// the output spent by input `i`, checked against its funding transaction
+fn spent_script(psbt: &PartiallySignedTransaction, i: usize) -> Result<Script, Error> {
+    let outpoint = psbt.global.unsigned_tx.input[i].previous_output;
+    let prev = psbt.inputs[i].non_witness_utxo.as_ref().ok_or(format_err!(
+        "input {} is missing its funding transaction",
+        i
+    ))?;
+    ensure!(
+        prev.txid() == outpoint.txid,
+        "funding transaction of input {} does not match",
+        i
+    );
+    Ok(prev
+        .output
+        .get(outpoint.vout as usize)
+        .ok_or(format_err!("input {} spends a missing output", i))?
+        .script_pubkey
+        .clone())
+}
+
+/// builds the same transaction as `construct_unsigned` of `sender`, spending
+/// outputs paying to its first script, with each input's funding transaction
+/// (and `redeem_script`, for p2sh) attached
+pub fn create_psbt(
+    sender: &Sender,
+    redeem_script: Option<&Script>,
+    inputs: &[Vec<u8>],
+    outputs: &[(&str, Amount)],
+    op_return: Option<&[u8]>,
+    options: &TxOptions,
+    network: Network,
+) -> Result<Vec<u8>, Error> {
+    let funding = inputs
+        .iter()
+        .map(|i| encode::deserialize::<Transaction>(i).map(|tx| (tx.txid(), tx)))
+        .collect::<Result<HashMap<_, _>, _>>()?;
+    let unsigned = tx::construct_unsigned(
+        sender,
+        &Utxo::from_txs(inputs, &sender.scripts[0])?,
+        outputs,
+        op_return,
+        options,
+        network,
+    )?;
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(unsigned.tx)?;
+    for (vin, input) in psbt
+        .global
+        .unsigned_tx
+        .input
+        .iter()
+        .zip(psbt.inputs.iter_mut())
+    {
+        input.non_witness_utxo = funding.get(&vin.previous_output.txid).cloned();
+        input.redeem_script = redeem_script.cloned();
+        input.sighash_type = Some(SigHashType::All);
+    }
+    Ok(encode::serialize(&psbt))
+}
+
+/// adds a SIGHASH_ALL signature by `mpriv` to every input it can spend: p2pkh
+/// inputs paying to `pubkey` and p2sh multisig inputs whose redeem script
+/// includes it
+pub fn sign(psbt: &[u8], mpriv: &SecretKey, pubkey: &[u8]) -> Result<Vec<u8>, Error> {
+    use bitcoin_hashes::Hash;
+
+    let mut psbt = decode(psbt)?;
+    let pkh_script =
+        Script::from([&[0x76, 0xA9, 20][..], &hash160(pubkey), &[0x88, 0xAC][..]].concat());
+    let own = psbt_pubkey(pubkey)?;
+    let mut signed = 0;
+    for i in 0..psbt.inputs.len() {
+        let spent = spent_script(&psbt, i)?;
+        let script_code = if spent == pkh_script {
+            spent
+        } else if spent.is_p2sh() {
+            match psbt.inputs[i].redeem_script {
+                Some(ref redeem)
+                    if redeem.to_p2sh() == spent
+                        && MultisigWallet::from_redeem_script(redeem.as_bytes())
+                            .ok()
+                            .map(|w| {
+                                w.pubkeys().iter().any(|k| {
+                                    k.serialize_compressed()[..] == pubkey[..]
+                                        || k.serialize()[..] == pubkey[..]
+                                })
+                            })
+                            .unwrap_or(false) =>
+                {
+                    redeem.clone()
+                }
+                _ => continue,
+            }
+        } else {
+            continue;
+        };
+        // whoever prepared the psbt could otherwise get a signature leaving
+        // the outputs open to change
+        let sighash_type = psbt.inputs[i].sighash_type.unwrap_or(SigHashType::All);
+        ensure!(
+            sighash_type == SigHashType::All,
+            "input {} asks for sighash type {:?}, only All is signed",
+            i,
+            sighash_type
+        );
+        let sighash = psbt
+            .global
+            .unsigned_tx
+            .signature_hash(i, &script_code, sighash_type.as_u32())
+            .into_inner();
+        let (sig, _) = secp256k1::sign(&secp256k1::Message::parse(&sighash), mpriv);
+        let mut sig_der = sig.serialize_der().as_ref().to_vec();
+        sig_der.push(sighash_type.as_u32() as u8);
+        psbt.inputs[i].partial_sigs.insert(own, sig_der);
+        signed += 1;
+    }
+    ensure!(signed > 0, "no inputs can be signed by this key");
+    Ok(encode::serialize(&psbt))
+}
+
+/// merges the signatures of several copies of the same psbt
+pub fn combine_psbts(psbts: &[Vec<u8>]) -> Result<Vec<u8>, Error> {
+    let mut iter = psbts.iter();
+    let mut res = decode(iter.next().ok_or(format_err!("no psbts to combine"))?)?;
+    for psbt in iter {
+        res.merge(decode(psbt)?)?;
+    }
+    Ok(encode::serialize(&res))
+}
+
+/// turns the collected signatures into final scriptSigs, checking each one
+pub fn finalize_psbt(psbt: &[u8]) -> Result<Vec<u8>, Error> {
+    use bitcoin::blockdata::script::Builder;
+    use bitcoin_hashes::Hash;
+
+    let mut psbt = decode(psbt)?;
+    for i in 0..psbt.inputs.len() {
+        if psbt.inputs[i].final_script_sig.is_some() {
+            continue;
+        }
+        let spent = spent_script(&psbt, i)?;
+        let script_sig = if spent.is_p2pkh() {
+            let (pubkey, sig) = psbt.inputs[i]
+                .partial_sigs
+                .iter()
+                .map(|(k, s)| (pubkey_bytes(k), s))
+                .find(|(k, _)| hash160(k) == spent.as_bytes()[3..23])
+                .ok_or(format_err!("input {} is not signed", i))?;
+            let (sighash_type, der) = sig
+                .split_last()
+                .ok_or(format_err!("empty signature in input {}", i))?;
+            let msg = secp256k1::Message::parse(
+                &psbt
+                    .global
+                    .unsigned_tx
+                    .signature_hash(i, &spent, *sighash_type as u32)
+                    .into_inner(),
+            );
+            ensure!(
+                secp256k1::verify(
+                    &msg,
+                    &secp256k1::Signature::parse_der_lax(der)
+                        .map_err(|e| format_err!("{:?}", e))?,
+                    &secp256k1::PublicKey::parse_slice(&pubkey, None)
+                        .map_err(|e| format_err!("{:?}", e))?,
+                ),
+                "invalid signature in input {}",
+                i
+            );
+            Builder::new()
+                .push_slice(sig)
+                .push_slice(&pubkey)
+                .into_script()
+        } else if spent.is_p2sh() {
+            let redeem = psbt.inputs[i]
+                .redeem_script
+                .clone()
+                .ok_or(format_err!("input {} is missing its redeem script", i))?;
+            ensure!(
+                redeem.to_p2sh() == spent,
+                "redeem script of input {} does not match",
+                i
+            );
+            let wallet = MultisigWallet::from_redeem_script(redeem.as_bytes())?;
+            let partial_sigs = psbt.inputs[i]
+                .partial_sigs
+                .iter()
+                .map(|(k, s)| (pubkey_bytes(k), s))
+                .collect::<Vec<_>>();
+            let sigs = wallet
+                .pubkeys()
+                .iter()
+                .filter_map(|k| {
+                    partial_sigs
+                        .iter()
+                        .find(|(p, _)| {
+                            p[..] == k.serialize_compressed()[..] || p[..] == k.serialize()[..]
+                        })
+                        .map(|(_, s)| s.to_vec())
+                })
+                .take(wallet.threshold())
+                .collect::<Vec<_>>();
+            ensure!(
+                sigs.len() >= wallet.threshold(),
+                "input {} has {} of {} signatures",
+                i,
+                sigs.len(),
+                wallet.threshold()
+            );
+            let matched = tx::match_multisig_signatures(
+                &psbt.global.unsigned_tx,
+                i,
+                &redeem,
+                wallet.pubkeys(),
+                &sigs,
+            );
+            ensure!(matched.is_some(), "invalid signature in input {}", i);
+            sigs.iter()
+                .fold(Builder::new().push_slice(&[]), |b, s| b.push_slice(s))
+                .push_slice(redeem.as_bytes())
+                .into_script()
+        } else {
+            bail!("input {} is neither p2pkh nor p2sh", i);
+        };
+        let input = &mut psbt.inputs[i];
+        input.final_script_sig = Some(script_sig);
+        input.partial_sigs.clear();
+        input.sighash_type = None;
+        input.redeem_script = None;
+        input.hd_keypaths.clear();
+    }
+    Ok(encode::serialize(&psbt))
+}
+
+/// the signed network transaction of a finalized psbt
+pub fn extract_psbt(psbt: &[u8]) -> Result<Vec<u8>, Error> {
+    let psbt = decode(psbt)?;
+    for (i, input) in psbt.inputs.iter().enumerate() {
+        ensure!(
+            input.final_script_sig.is_some(),
+            "input {} is not finalized",
+            i
+        );
+    }
+    tx::serialize(&psbt.extract_tx())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::{script_to_addr, ChildWallet, Fee};
+    use bitcoin::{OutPoint, TxIn, TxOut};
+
+    const NETWORK: Network = Network::Dogecoin;
+
+    /// a transaction paying `value` to `script`, made unique by `salt`
+    fn fund(script: Script, value: u64, salt: u32) -> Vec<u8> {
+        encode::serialize(&Transaction {
+            version: 1,
+            lock_time: salt,
+            input: vec![TxIn {
+                previous_output: OutPoint::default(),
+                script_sig: Script::new(),
+                sequence: 0xFFFFFFFF,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: script,
+            }],
+        })
+    }
+
+    fn wallet(n: u8) -> ChildWallet {
+        ChildWallet::new([n; 64])
+    }
+
+    fn psbt_of(
+        script: &Script,
+        script_sig_len: usize,
+        redeem_script: Option<&Script>,
+        inputs: &[Vec<u8>],
+    ) -> Vec<u8> {
+        let dest = wallet(9).address(NETWORK);
+        create_psbt(
+            &Sender {
+                scripts: std::slice::from_ref(script),
+                script_sig_len,
+                address: &script_to_addr(script, NETWORK).unwrap(),
+            },
+            redeem_script,
+            inputs,
+            &[(&dest, Amount::from_sat(1_000_000))],
+            Some(&b"psbt"[..]),
+            &TxOptions::new(Fee::Flat(100_000)),
+            NETWORK,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn p2pkh_round_trip() {
+        let key = wallet(1).to_key_wallet();
+        let inputs = vec![
+            fund(key.script(), 10_000_000, 1),
+            fund(key.script(), 3_000_000, 2),
+        ];
+        let psbt = psbt_of(&key.script(), key.script_sig_len(), None, &inputs);
+        assert!(finalize_psbt(&psbt).is_err());
+        assert!(extract_psbt(&psbt).is_err());
+        assert!(wallet(2).key().sign_psbt(&psbt).is_err());
+
+        let signed = key.sign_psbt(&psbt).unwrap();
+        let tx = extract_psbt(&finalize_psbt(&signed).unwrap()).unwrap();
+        let direct = key
+            .construct_signed(
+                &inputs,
+                &[(&wallet(9).address(NETWORK), Amount::from_sat(1_000_000))],
+                Amount::from_sat(100_000),
+                Some(&b"psbt"[..]),
+                NETWORK,
+            )
+            .unwrap();
+        assert_eq!(tx, direct);
+    }
+
+    #[test]
+    fn multisig_combine_finalize() {
+        let cosigners = [wallet(1), wallet(2), wallet(3)];
+        let ms =
+            MultisigWallet::from_child_wallets(2, &[&cosigners[0], &cosigners[1], &cosigners[2]])
+                .unwrap();
+        let inputs = vec![fund(ms.script(), 10_000_000, 3)];
+        let psbt = psbt_of(
+            &ms.script(),
+            ms.script_sig_len(),
+            Some(ms.redeem_script()),
+            &inputs,
+        );
+
+        let a = cosigners[2].key().sign_psbt(&psbt).unwrap();
+        assert!(finalize_psbt(&a).is_err());
+        let b = cosigners[0].key().sign_psbt(&psbt).unwrap();
+        let combined = combine_psbts(&[a.clone(), b]).unwrap();
+        let tx = extract_psbt(&finalize_psbt(&combined).unwrap()).unwrap();
+        // combining in a signature again changes nothing
+        let again = combine_psbts(&[combined, a]).unwrap();
+        assert_eq!(extract_psbt(&finalize_psbt(&again).unwrap()).unwrap(), tx);
+
+        assert!(ms.is_complete(&tx).unwrap());
+        let direct = ms
+            .sign(
+                &cosigners[2],
+                &ms.construct_signed(
+                    &cosigners[0],
+                    &inputs,
+                    &[(&wallet(9).address(NETWORK), Amount::from_sat(1_000_000))],
+                    Amount::from_sat(100_000),
+                    Some(&b"psbt"[..]),
+                    NETWORK,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        assert_eq!(tx, direct);
+    }
+
+    #[test]
+    fn combine_different_transactions() {
+        let key = wallet(1).to_key_wallet();
+        let a = psbt_of(
+            &key.script(),
+            key.script_sig_len(),
+            None,
+            &[fund(key.script(), 10_000_000, 1)],
+        );
+        let b = psbt_of(
+            &key.script(),
+            key.script_sig_len(),
+            None,
+            &[fund(key.script(), 10_000_000, 2)],
+        );
+        assert!(combine_psbts(&[a, b]).is_err());
+        assert!(combine_psbts(&[]).is_err());
+    }
+}
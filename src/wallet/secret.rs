@@ -0,0 +1,37 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use zeroize::Zeroize;
+
+/// Key material that is wiped when dropped and never printed by `Debug`.
+#[derive(Clone)]
+pub struct Secret<T: Zeroize>(T);
+impl<T: Zeroize> Secret<T> {
+    pub fn new(inner: T) -> Self {
+        Secret(inner)
+    }
+}
+impl<T: Zeroize> Deref for Secret<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+impl<T: Zeroize> DerefMut for Secret<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+impl<T: Zeroize> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret([REDACTED])")
+    }
+}
+
+pub type Entropy = Secret<[u8; 16]>;
+pub type Seed = Secret<[u8; 64]>;
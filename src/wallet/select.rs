@@ -0,0 +1,128 @@
+use super::error::TxBuildError;
+use super::tx::Utxo;
+use failure::Error;
+
+/// how many branches `branch_and_bound` explores before falling back
+const BNB_MAX_TRIES: usize = 100_000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CoinSelection {
+    /// spend every output paying to the wallet
+    All,
+    LargestFirst,
+    /// look for an input set needing no change, falling back to largest first
+    #[default]
+    BranchAndBound,
+}
+
+/// Picks inputs worth at least `target`. Sums within `cost_of_change` above
+/// the target are spent without a change output, so branch and bound looks
+/// for the one wasting the least.
+pub fn select(
+    mut utxos: Vec<Utxo>,
+    target: u64,
+    cost_of_change: u64,
+    strategy: CoinSelection,
+) -> Result<Vec<Utxo>, Error> {
+    let total = utxos
+        .iter()
+        .try_fold(0u64, |acc, u| acc.checked_add(u.output.value))
+        .ok_or(TxBuildError::Overflow { what: "input" })?;
+    if total < target {
+        return Err(TxBuildError::InsufficientFunds {
+            needed: target,
+            available: total,
+        }
+        .into());
+    }
+    utxos.sort_by(|a, b| {
+        b.output
+            .value
+            .cmp(&a.output.value)
+            .then(a.outpoint.cmp(&b.outpoint))
+    });
+
+    match strategy {
+        CoinSelection::All => Ok(utxos),
+        CoinSelection::LargestFirst => Ok(largest_first(utxos, target)),
+        CoinSelection::BranchAndBound => {
+            match branch_and_bound(&utxos, total, target, cost_of_change) {
+                Some(selection) => Ok(utxos
+                    .into_iter()
+                    .zip(selection)
+                    .filter(|(_, selected)| *selected)
+                    .map(|(u, _)| u)
+                    .collect()),
+                None => Ok(largest_first(utxos, target)),
+            }
+        }
+    }
+}
+
+/// `utxos` must be sorted by descending value
+fn largest_first(utxos: Vec<Utxo>, target: u64) -> Vec<Utxo> {
+    let mut value = 0;
+    utxos
+        .into_iter()
+        .take_while(|u| {
+            let needed = value < target;
+            value += u.output.value;
+            needed
+        })
+        .collect()
+}
+
+/// Depth first search over including or excluding each output, as in Bitcoin
+/// Core. `utxos` must be sorted by descending value; returns which to spend.
+fn branch_and_bound(
+    utxos: &[Utxo],
+    total: u64,
+    target: u64,
+    cost_of_change: u64,
+) -> Option<Vec<bool>> {
+    let values = utxos.iter().map(|u| u.output.value).collect::<Vec<_>>();
+    let mut value = 0;
+    let mut available = total;
+    let mut selection: Vec<bool> = Vec::with_capacity(values.len());
+    let mut best: Option<(u64, Vec<bool>)> = None;
+
+    for _ in 0..BNB_MAX_TRIES {
+        let backtrack =
+            if value + available < target || value > target.saturating_add(cost_of_change) {
+                true
+            } else if value >= target {
+                let waste = value - target;
+                if best.as_ref().map(|b| waste <= b.0).unwrap_or(true) {
+                    let mut full = selection.clone();
+                    full.resize(values.len(), false);
+                    best = Some((waste, full));
+                    if waste == 0 {
+                        break;
+                    }
+                }
+                true
+            } else {
+                false
+            };
+
+        if backtrack {
+            while let Some(false) = selection.last() {
+                selection.pop();
+                available += values[selection.len()];
+            }
+            match selection.last_mut() {
+                Some(last) => *last = false,
+                None => break,
+            }
+            value -= values[selection.len() - 1];
+        } else {
+            let i = selection.len();
+            available -= values[i];
+            value += values[i];
+            selection.push(true);
+        }
+    }
+
+    best.map(|b| b.1)
+}
@@ -0,0 +1,440 @@
+//! SLIP-39 style two-level Shamir secret sharing of wallet entropy.
+//!
+//! The entropy is split into group shares, and each group share is split again
+//! among that group's members. As in SLIP-39 the secret sits at x = 255 and a
+//! digest of it at x = 254, so a wrong or mismatched share is detected when
+//! combining instead of silently producing a different wallet. Shares are
+//! written with the BIP39 word list and carry their own checksum.
+
+use super::consts;
+use super::Secret;
+use failure::Error;
+use hmac::Mac;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+pub type HmacSha256 = hmac::Hmac<sha2::Sha256>;
+type Point = (u8, Secret<Vec<u8>>);
+
+pub const MAX_SHARES: u8 = 16;
+const SECRET_INDEX: u8 = 255;
+const DIGEST_INDEX: u8 = 254;
+const DIGEST_LEN: usize = 4;
+const CHECKSUM_LEN: usize = 4;
+const CHECKSUM_PREFIX: &[u8] = b"borker share";
+/// id, group and member parameters, 16 bytes of entropy, checksum
+const SHARE_LEN: usize = 2 + 3 + 16 + CHECKSUM_LEN;
+const SHARE_WORDS: usize = (SHARE_LEN * 8).div_ceil(11);
+
+#[derive(Clone, Debug)]
+pub struct Share {
+    pub identifier: u16,
+    pub group_index: u8,
+    pub group_threshold: u8,
+    pub group_count: u8,
+    pub member_index: u8,
+    pub member_threshold: u8,
+    value: Secret<Vec<u8>>,
+}
+impl Share {
+    fn checksum(data: &[u8]) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        hasher.input(CHECKSUM_PREFIX);
+        hasher.input(data);
+        hasher.result()[0..CHECKSUM_LEN].to_vec()
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut res = Vec::with_capacity(SHARE_LEN);
+        res.extend_from_slice(&self.identifier.to_be_bytes());
+        res.push(self.group_index << 4 | (self.group_threshold - 1));
+        res.push((self.group_count - 1) << 4 | self.member_index);
+        res.push((self.member_threshold - 1) << 4);
+        res.extend_from_slice(&self.value);
+        let checksum = Self::checksum(&res);
+        res.extend(checksum);
+        res
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        ensure!(bytes.len() == SHARE_LEN, "invalid share length");
+        let (data, checksum) = bytes.split_at(SHARE_LEN - CHECKSUM_LEN);
+        ensure!(
+            Self::checksum(data) == checksum,
+            "share checksum verification failed"
+        );
+        ensure!(data[4] & 0x0F == 0, "invalid share padding");
+        let res = Share {
+            identifier: u16::from_be_bytes([data[0], data[1]]),
+            group_index: data[2] >> 4,
+            group_threshold: (data[2] & 0x0F) + 1,
+            group_count: (data[3] >> 4) + 1,
+            member_index: data[3] & 0x0F,
+            member_threshold: (data[4] >> 4) + 1,
+            value: Secret::new(data[5..].to_vec()),
+        };
+        ensure!(
+            res.group_threshold <= res.group_count && res.group_index < res.group_count,
+            "invalid group parameters"
+        );
+        Ok(res)
+    }
+
+    pub fn words(&self) -> Vec<&'static str> {
+        let bytes = Secret::new(self.as_bytes());
+        (0..SHARE_WORDS)
+            .map(|w| {
+                let mut idx: usize = 0;
+                for bit in w * 11..(w + 1) * 11 {
+                    let b = bytes.get(bit / 8).map(|b| b >> (7 - bit % 8) & 1);
+                    idx = idx << 1 | b.unwrap_or(0) as usize;
+                }
+                consts::DICT[idx]
+            })
+            .collect()
+    }
+
+    pub fn from_words(words: &[String]) -> Result<Self, Error> {
+        ensure!(
+            words.len() == SHARE_WORDS,
+            "share must be {} words",
+            SHARE_WORDS
+        );
+        let mut bytes = Secret::new(vec![0; SHARE_LEN]);
+        for (w, word) in words.iter().enumerate() {
+            let idx = consts::DICT
+                .binary_search(&word.as_str())
+                .map_err(|_| format_err!("{} is not a valid bip39 word", word))?;
+            for bit in 0..11 {
+                let pos = w * 11 + bit;
+                let b = (idx >> (10 - bit) & 1) as u8;
+                match bytes.get_mut(pos / 8) {
+                    Some(byte) => *byte |= b << (7 - pos % 8),
+                    None => ensure!(b == 0, "invalid share padding"),
+                }
+            }
+        }
+        Self::from_bytes(&bytes)
+    }
+}
+
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut res = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            res ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1B;
+        }
+        b >>= 1;
+    }
+    res
+}
+
+fn gf_inv(a: u8) -> u8 {
+    // a^254 == a^-1 in GF(256)
+    let mut res = 1;
+    let mut base = a;
+    let mut exp = 254;
+    while exp > 0 {
+        if exp & 1 != 0 {
+            res = gf_mul(res, base);
+        }
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+    res
+}
+
+/// evaluates the polynomial through `points` at `x`, bytewise
+fn interpolate(points: &[Point], x: u8) -> Result<Secret<Vec<u8>>, Error> {
+    let len = points
+        .first()
+        .map(|p| p.1.len())
+        .ok_or(format_err!("no shares"))?;
+    ensure!(
+        points.iter().all(|p| p.1.len() == len),
+        "share lengths do not match"
+    );
+    if let Some(p) = points.iter().find(|p| p.0 == x) {
+        return Ok(p.1.clone());
+    }
+    let mut res = Secret::new(vec![0; len]);
+    for (i, (xi, yi)) in points.iter().enumerate() {
+        let mut basis = 1;
+        for (j, (xj, _)) in points.iter().enumerate() {
+            if i != j {
+                ensure!(xi != xj, "duplicate share index {}", xi);
+                basis = gf_mul(basis, gf_mul(x ^ xj, gf_inv(xi ^ xj)));
+            }
+        }
+        for (r, y) in res.iter_mut().zip(yi.iter()) {
+            *r ^= gf_mul(basis, *y);
+        }
+    }
+    Ok(res)
+}
+
+fn digest(random: &[u8], secret: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_varkey(random).unwrap();
+    mac.input(secret);
+    mac.result().code()[0..DIGEST_LEN].to_vec()
+}
+
+fn split_secret(threshold: u8, count: u8, secret: &[u8]) -> Result<Vec<Secret<Vec<u8>>>, Error> {
+    use rand::RngCore;
+
+    ensure!(
+        0 < threshold && threshold <= count && count <= MAX_SHARES,
+        "invalid threshold {} of {}",
+        threshold,
+        count
+    );
+    if threshold == 1 {
+        return Ok(vec![Secret::new(secret.to_vec()); count as usize]);
+    }
+    let mut rng = rand::thread_rng();
+    let mut points = (0..threshold - 2)
+        .map(|x| {
+            let mut share = Secret::new(vec![0; secret.len()]);
+            rng.fill_bytes(&mut share);
+            (x, share)
+        })
+        .collect::<Vec<_>>();
+    let mut random = vec![0; secret.len() - DIGEST_LEN];
+    rng.fill_bytes(&mut random);
+    let mut digest_share = Secret::new(Vec::with_capacity(secret.len()));
+    digest_share.extend_from_slice(&digest(&random, secret));
+    digest_share.extend_from_slice(&random);
+    points.push((DIGEST_INDEX, digest_share));
+    points.push((SECRET_INDEX, Secret::new(secret.to_vec())));
+
+    let mut res = points[0..threshold as usize - 2]
+        .iter()
+        .map(|p| p.1.clone())
+        .collect::<Vec<_>>();
+    for x in threshold - 2..count {
+        res.push(interpolate(&points, x)?);
+    }
+    Ok(res)
+}
+
+fn recover_secret(threshold: u8, points: &[Point]) -> Result<Secret<Vec<u8>>, Error> {
+    ensure!(
+        points.len() >= threshold as usize,
+        "need {} shares, got {}",
+        threshold,
+        points.len()
+    );
+    let points = &points[0..threshold as usize];
+    if threshold == 1 {
+        return Ok(points[0].1.clone());
+    }
+    let secret = interpolate(points, SECRET_INDEX)?;
+    let digest_share = interpolate(points, DIGEST_INDEX)?;
+    ensure!(
+        digest(&digest_share[DIGEST_LEN..], &secret) == digest_share[..DIGEST_LEN],
+        "share digest verification failed"
+    );
+    Ok(secret)
+}
+
+/// `groups` lists (member_threshold, member_count) for each group
+pub fn split(
+    secret: &[u8],
+    group_threshold: u8,
+    groups: &[(u8, u8)],
+) -> Result<Vec<Vec<Share>>, Error> {
+    use rand::Rng;
+
+    ensure!(secret.len() == 16, "secret must be 16 bytes");
+    ensure!(groups.len() <= MAX_SHARES as usize, "too many groups");
+    let identifier: u16 = rand::thread_rng().gen();
+    let group_count = groups.len() as u8;
+    let group_secrets = split_secret(group_threshold, group_count, secret)?;
+
+    groups
+        .iter()
+        .zip(group_secrets)
+        .enumerate()
+        .map(
+            |(group_index, (&(member_threshold, member_count), group_secret))| {
+                ensure!(
+                    member_threshold > 1 || member_count == 1,
+                    "a group with a threshold of 1 must have a single member"
+                );
+                Ok(split_secret(member_threshold, member_count, &group_secret)?
+                    .into_iter()
+                    .enumerate()
+                    .map(|(member_index, value)| Share {
+                        identifier,
+                        group_index: group_index as u8,
+                        group_threshold,
+                        group_count,
+                        member_index: member_index as u8,
+                        member_threshold,
+                        value,
+                    })
+                    .collect())
+            },
+        )
+        .collect()
+}
+
+pub fn combine(shares: &[Share]) -> Result<Secret<Vec<u8>>, Error> {
+    let first = shares.first().ok_or(format_err!("no shares"))?;
+    let mut groups: BTreeMap<u8, (u8, Vec<Point>)> = BTreeMap::new();
+    for share in shares {
+        ensure!(
+            share.identifier == first.identifier
+                && share.group_threshold == first.group_threshold
+                && share.group_count == first.group_count,
+            "shares do not belong to the same backup"
+        );
+        let group = groups
+            .entry(share.group_index)
+            .or_insert((share.member_threshold, Vec::new()));
+        ensure!(
+            group.0 == share.member_threshold,
+            "member thresholds do not match in group {}",
+            share.group_index
+        );
+        if !group.1.iter().any(|p| p.0 == share.member_index) {
+            group.1.push((share.member_index, share.value.clone()));
+        }
+    }
+
+    let group_secrets = groups
+        .into_iter()
+        .filter(|(_, (threshold, members))| members.len() >= *threshold as usize)
+        .map(|(index, (threshold, members))| Ok((index, recover_secret(threshold, &members)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    ensure!(
+        group_secrets.len() >= first.group_threshold as usize,
+        "need {} complete groups, got {}",
+        first.group_threshold,
+        group_secrets.len()
+    );
+    recover_secret(first.group_threshold, &group_secrets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: [u8; 16] = *b"borker entropy!!";
+
+    fn combined(shares: &[&Share]) -> Result<Vec<u8>, Error> {
+        let shares = shares.iter().map(|s| (*s).clone()).collect::<Vec<_>>();
+        Ok(combine(&shares)?.to_vec())
+    }
+
+    #[test]
+    fn single_group() {
+        let groups = split(&SECRET, 1, &[(3, 5)]).unwrap();
+        let g = &groups[0];
+        assert_eq!(g.len(), 5);
+        assert_eq!(combined(&[&g[0], &g[1], &g[2]]).unwrap(), SECRET);
+        assert_eq!(combined(&[&g[4], &g[1], &g[3]]).unwrap(), SECRET);
+        assert_eq!(
+            combined(&[&g[0], &g[1], &g[2], &g[3], &g[4]]).unwrap(),
+            SECRET
+        );
+        assert!(combined(&[&g[0], &g[1]]).is_err());
+        // the same share twice counts once
+        assert!(combined(&[&g[0], &g[1], &g[1]]).is_err());
+    }
+
+    #[test]
+    fn groups() {
+        let groups = split(&SECRET, 2, &[(2, 3), (1, 1), (3, 5)]).unwrap();
+        assert_eq!(
+            combined(&[&groups[0][2], &groups[0][0], &groups[1][0]]).unwrap(),
+            SECRET
+        );
+        assert_eq!(
+            combined(&[&groups[1][0], &groups[2][0], &groups[2][3], &groups[2][4]]).unwrap(),
+            SECRET
+        );
+        // one complete group, and one short of its threshold
+        assert!(combined(&[&groups[0][0], &groups[0][1], &groups[2][0], &groups[2][1]]).is_err());
+        assert!(combined(&[&groups[1][0]]).is_err());
+    }
+
+    #[test]
+    fn thresholds() {
+        assert_eq!(split(&SECRET, 1, &[(1, 1)]).unwrap()[0].len(), 1);
+        let all = split(&SECRET, 1, &[(16, 16)]).unwrap();
+        assert_eq!(
+            combined(&all[0].iter().collect::<Vec<_>>()).unwrap(),
+            SECRET
+        );
+        assert!(split(&SECRET, 1, &[(0, 3)]).is_err());
+        assert!(split(&SECRET, 1, &[(4, 3)]).is_err());
+        assert!(split(&SECRET, 1, &[(2, 17)]).is_err());
+        assert!(split(&SECRET, 1, &[(1, 2)]).is_err());
+        assert!(split(&SECRET, 2, &[(2, 3)]).is_err());
+        assert!(split(&SECRET[..15], 1, &[(2, 3)]).is_err());
+        assert!(combine(&[]).is_err());
+    }
+
+    #[test]
+    fn mixed_backups() {
+        let a = split(&SECRET, 1, &[(2, 3)]).unwrap();
+        let mut b = split(&SECRET, 1, &[(2, 3)]).unwrap();
+        b[0][1].identifier = a[0][0].identifier.wrapping_add(1);
+        assert!(combined(&[&a[0][0], &b[0][1]]).is_err());
+    }
+
+    #[test]
+    fn words_round_trip() {
+        let share = split(&SECRET, 1, &[(2, 3)]).unwrap().remove(0).remove(1);
+        let words = share
+            .words()
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        assert_eq!(words.len(), SHARE_WORDS);
+        let restored = Share::from_words(&words).unwrap();
+        assert_eq!(restored.as_bytes(), share.as_bytes());
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let shares = split(&SECRET, 1, &[(2, 3)]).unwrap().remove(0);
+        let mut bytes = shares[0].as_bytes();
+        bytes[7] ^= 1;
+        assert_eq!(
+            Share::from_bytes(&bytes).unwrap_err().to_string(),
+            "share checksum verification failed"
+        );
+
+        let mut words = shares[0]
+            .words()
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        words[3] = if words[3] == "abandon" {
+            "ability"
+        } else {
+            "abandon"
+        }
+        .to_owned();
+        assert!(Share::from_words(&words).is_err());
+    }
+
+    #[test]
+    fn bad_digest() {
+        let mut shares = split(&SECRET, 1, &[(2, 3)]).unwrap().remove(0);
+        // a share altered along with its checksum still fails the digest
+        shares[0].value[0] ^= 1;
+        let altered = Share::from_bytes(&shares[0].as_bytes()).unwrap();
+        assert_eq!(
+            combined(&[&altered, &shares[1]]).unwrap_err().to_string(),
+            "share digest verification failed"
+        );
+    }
+}
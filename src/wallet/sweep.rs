@@ -0,0 +1,107 @@
+//! Moving everything some keys can spend to one address, e.g. off a paper
+//! wallet or an old derivation path. There is no amount and no change: the
+//! fee comes out of the total.
+
+use super::addr_to_script;
+use super::amount::Amount;
+use super::error::TxBuildError;
+use super::fee;
+use super::multikey;
+use super::tx::{BuiltTx, TxOptions, Utxo};
+use super::verify;
+use super::KeyWallet;
+use crate::Network;
+use bitcoin::{Transaction, TxIn, TxOut};
+use failure::Error;
+use std::collections::HashSet;
+
+/// spends all of `utxos`, each paying to one of `keys`, to `address`
+pub fn sweep(
+    keys: &[KeyWallet],
+    utxos: &[Utxo],
+    address: &str,
+    options: &TxOptions,
+    network: Network,
+) -> Result<BuiltTx, Error> {
+    let scripts = keys.iter().map(|k| k.script()).collect::<Vec<_>>();
+    let mut seen = HashSet::new();
+    // each output along with the key spending it
+    let mut inputs = Vec::new();
+    for utxo in utxos {
+        let key = scripts
+            .iter()
+            .position(|s| s == &utxo.output.script_pubkey)
+            .ok_or(TxBuildError::ForeignInput {
+                txid: format!("{:x}", utxo.outpoint.txid),
+                vout: utxo.outpoint.vout,
+            })?;
+        if seen.insert(utxo.outpoint) {
+            inputs.push((utxo, key));
+        }
+    }
+    if inputs.is_empty() {
+        return Err(TxBuildError::NoMatchingInputs {
+            reason: "nothing to sweep".to_owned(),
+        }
+        .into());
+    }
+
+    let total = inputs
+        .iter()
+        .try_fold(0u64, |acc, (u, _)| acc.checked_add(u.output.value))
+        .ok_or(TxBuildError::Overflow { what: "input" })?;
+    let script_pubkey = addr_to_script(address, network)?;
+    let script_sig_len = inputs
+        .iter()
+        .map(|(_, k)| fee::p2pkh_script_sig_len(keys[*k].pubkey().len()))
+        .max()
+        .unwrap_or(0);
+    let size = fee::estimate_size(inputs.len(), script_sig_len, &[script_pubkey.len()]);
+    if size > fee::MAX_STANDARD_TX_SIZE {
+        return Err(TxBuildError::TooLarge {
+            size,
+            max: fee::MAX_STANDARD_TX_SIZE,
+        }
+        .into());
+    }
+    let fee = options.fee.amount(size, network);
+    let dust = fee::dust_threshold(script_pubkey.len(), network);
+    // the fee plus an output worth keeping
+    let needed = fee.saturating_add(dust);
+    if total < needed {
+        return Err(TxBuildError::InsufficientFunds {
+            needed,
+            available: total,
+        }
+        .into());
+    }
+    let value = total - fee;
+
+    let mut tx = Transaction {
+        version: 1,
+        lock_time: 0,
+        input: inputs
+            .iter()
+            .map(|(u, _)| TxIn {
+                previous_output: u.outpoint,
+                script_sig: bitcoin::Script::new(),
+                sequence: options.sequence(),
+                witness: vec![],
+            })
+            .collect(),
+        output: vec![TxOut {
+            script_pubkey,
+            value,
+        }],
+    };
+    multikey::sign_with_keys(&mut tx, keys, utxos)?;
+    verify::verify_inputs(&tx, utxos, network)?;
+
+    Ok(BuiltTx {
+        spent: tx.input.iter().map(|i| i.previous_output).collect(),
+        tx,
+        fee: Amount::from_sat(fee),
+        change: Amount::ZERO,
+        change_vout: None,
+    })
+}
@@ -0,0 +1,582 @@
+use super::addr_to_script;
+use super::amount::Amount;
+use super::error::TxBuildError;
+use super::fee::{self, Fee};
+use super::pubkey_to_addr;
+use super::select::{self, CoinSelection};
+use crate::Network;
+use bitcoin::blockdata::opcodes;
+use bitcoin::blockdata::script::Builder;
+use bitcoin::{OutPoint, Script, Transaction, TxIn, TxOut};
+use failure::Error;
+use secp256k1::{PublicKey, SecretKey};
+use std::collections::HashSet;
+
+/// an output to spend, as reported by an explorer or found in a block
+#[derive(Clone, Debug)]
+pub struct Utxo {
+    pub outpoint: OutPoint,
+    pub output: TxOut,
+    /// the funding transaction, if known
+    pub tx: Option<Transaction>,
+}
+impl Utxo {
+    /// `txid` is hex in the usual reversed byte order
+    pub fn new(txid: &str, vout: u32, value: u64, script: Script) -> Result<Self, Error> {
+        use bitcoin_hashes::{sha256d, Hash};
+
+        let mut txid = hex::decode(txid)?;
+        ensure!(txid.len() == 32, "invalid txid");
+        txid.reverse();
+        Ok(Utxo {
+            outpoint: OutPoint {
+                txid: sha256d::Hash::from_slice(&txid)?,
+                vout,
+            },
+            output: TxOut {
+                value,
+                script_pubkey: script,
+            },
+            tx: None,
+        })
+    }
+
+    /// attaches the funding transaction, checking the output against it
+    pub fn with_tx(mut self, tx: &[u8]) -> Result<Self, Error> {
+        let tx: Transaction = bitcoin::consensus::deserialize(tx)?;
+        ensure!(
+            tx.txid() == self.outpoint.txid
+                && tx.output.get(self.outpoint.vout as usize) == Some(&self.output),
+            "output {}:{} does not match its funding transaction",
+            self.outpoint.txid,
+            self.outpoint.vout
+        );
+        self.tx = Some(tx);
+        Ok(self)
+    }
+
+    /// every output of the raw transaction `tx` paying to `script`
+    pub fn from_tx(tx: &[u8], script: &Script) -> Result<Vec<Self>, Error> {
+        Ok(Self::outputs_of(tx)?
+            .into_iter()
+            .filter(|u| &u.output.script_pubkey == script)
+            .collect())
+    }
+
+    /// every output of `tx`, whatever it pays to
+    pub fn outputs_of(tx: &[u8]) -> Result<Vec<Self>, Error> {
+        let tx: Transaction = bitcoin::consensus::deserialize(tx)?;
+        Ok(tx
+            .output
+            .iter()
+            .enumerate()
+            .map(|(vout, o)| Utxo {
+                outpoint: OutPoint {
+                    txid: tx.txid(),
+                    vout: vout as u32,
+                },
+                output: o.clone(),
+                tx: Some(tx.clone()),
+            })
+            .collect())
+    }
+
+    pub fn from_txs(txs: &[Vec<u8>], script: &Script) -> Result<Vec<Self>, Error> {
+        Ok(txs
+            .iter()
+            .map(|tx| Self::from_tx(tx, script))
+            .collect::<Result<Vec<_>, _>>()?
+            .concat())
+    }
+}
+
+/// the highest sequence number signalling replaceability
+pub const RBF_SEQUENCE: u32 = 0xFFFFFFFD;
+
+/// where change goes; below the dust threshold it is left to the miners
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangePolicy {
+    /// back to the address being spent from
+    #[default]
+    Sender,
+    /// e.g. an unused address of the account's change chain
+    Address(String),
+}
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TxOptions {
+    pub fee: Fee,
+    #[serde(default)]
+    pub coin_selection: CoinSelection,
+    #[serde(default)]
+    pub change: ChangePolicy,
+    /// signal that the transaction may be replaced by one paying more (BIP125)
+    #[serde(default)]
+    pub rbf: bool,
+    /// the largest OP_RETURN script the nodes broadcast to relay, if not the
+    /// network's default
+    #[serde(default)]
+    pub data_carrier_size: Option<usize>,
+}
+impl TxOptions {
+    pub fn new(fee: Fee) -> Self {
+        TxOptions {
+            fee,
+            coin_selection: CoinSelection::default(),
+            change: ChangePolicy::default(),
+            rbf: false,
+            data_carrier_size: None,
+        }
+    }
+
+    /// the sequence number of every input
+    pub fn sequence(&self) -> u32 {
+        if self.rbf {
+            RBF_SEQUENCE
+        } else {
+            0xFFFFFFFF
+        }
+    }
+
+    /// the most data an OP_RETURN may carry and still be relayed
+    pub fn max_op_return_len(&self, network: Network) -> usize {
+        fee::max_relayed_op_return_len(
+            self.data_carrier_size
+                .unwrap_or_else(|| fee::data_carrier_size(network)),
+        )
+    }
+
+    /// where change from `address` goes
+    pub fn change_script(&self, address: &str, network: Network) -> Result<Script, Error> {
+        addr_to_script(
+            match self.change {
+                ChangePolicy::Sender => address,
+                ChangePolicy::Address(ref addr) => addr,
+            },
+            network,
+        )
+    }
+}
+
+/// a constructed transaction along with the outputs it spends, so they can be
+/// marked as spent
+#[derive(Clone, Debug)]
+pub struct BuiltTx {
+    pub tx: Transaction,
+    pub spent: Vec<OutPoint>,
+    pub fee: Amount,
+    /// zero if there is no change output
+    pub change: Amount,
+    pub change_vout: Option<u32>,
+}
+impl BuiltTx {
+    pub fn txid(&self) -> String {
+        format!("{:x}", self.tx.txid())
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>, Error> {
+        serialize(&self.tx)
+    }
+
+    /// the change output, for a transaction spending it
+    pub fn change_utxo(&self) -> Option<Utxo> {
+        self.change_vout.map(|vout| Utxo {
+            outpoint: OutPoint {
+                txid: self.tx.txid(),
+                vout,
+            },
+            output: self.tx.output[vout as usize].clone(),
+            tx: Some(self.tx.clone()),
+        })
+    }
+}
+
+/// who a transaction is built for: the outputs it may spend and where its
+/// change goes by default
+#[derive(Clone, Copy, Debug)]
+pub struct Sender<'a> {
+    /// scripts of the outputs it may spend, the sender's own first
+    pub scripts: &'a [Script],
+    /// the size of each signed scriptSig, for fee estimates
+    pub script_sig_len: usize,
+    pub address: &'a str,
+}
+
+/// `construct_unsigned` for a single P2PKH key, spending outputs paying to
+/// `pubkey` and sending change back to it
+pub fn construct_p2pkh_unsigned(
+    pubkey: &[u8],
+    utxos: &[Utxo],
+    outputs: &[(&str, Amount)],
+    op_return: Option<&[u8]>,
+    options: &TxOptions,
+    network: Network,
+) -> Result<BuiltTx, Error> {
+    let address = pubkey_to_addr(pubkey, network);
+    construct_unsigned(
+        &Sender {
+            scripts: &[addr_to_script(&address, network)?],
+            script_sig_len: fee::p2pkh_script_sig_len(pubkey.len()),
+            address: &address,
+        },
+        utxos,
+        outputs,
+        op_return,
+        options,
+        network,
+    )
+}
+
+/// Builds a transaction spending outputs paying to any of `sender`'s scripts.
+/// The fee is estimated from the signed size and inputs are reselected until
+/// it covers them. Input 0 always spends an output paying to the first script,
+/// the sender's own.
+pub fn construct_unsigned(
+    sender: &Sender,
+    utxos: &[Utxo],
+    outputs: &[(&str, Amount)],
+    op_return: Option<&[u8]>,
+    options: &TxOptions,
+    network: Network,
+) -> Result<BuiltTx, Error> {
+    let mut seen = HashSet::new();
+    let mut unique = Vec::new();
+    for utxo in utxos {
+        if !sender.scripts.contains(&utxo.output.script_pubkey) {
+            return Err(TxBuildError::ForeignInput {
+                txid: format!("{:x}", utxo.outpoint.txid),
+                vout: utxo.outpoint.vout,
+            }
+            .into());
+        }
+        if seen.insert(utxo.outpoint) {
+            unique.push(utxo.clone());
+        }
+    }
+    let output_size = Amount::checked_sum(outputs.iter().map(|o| o.1))
+        .ok_or(TxBuildError::Overflow { what: "output" })?
+        .as_sat();
+    let mut output = outputs
+        .iter()
+        .map(|(addr, val)| -> Result<_, Error> {
+            Ok(TxOut {
+                script_pubkey: addr_to_script(addr, network)?,
+                value: val.as_sat(),
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    for (i, o) in output.iter().enumerate() {
+        let dust = fee::dust_threshold(o.script_pubkey.len(), network);
+        if o.value < dust {
+            return Err(TxBuildError::DustOutput {
+                index: i,
+                value: o.value,
+                threshold: dust,
+            }
+            .into());
+        }
+    }
+    if let Some(data) = op_return {
+        let max = options.max_op_return_len(network);
+        if data.len() > max {
+            return Err(TxBuildError::OpReturnTooLarge {
+                len: data.len(),
+                max,
+            }
+            .into());
+        }
+    }
+    let op_return = op_return.map(|data| TxOut {
+        script_pubkey: Builder::new()
+            .push_opcode(opcodes::all::OP_RETURN)
+            .push_slice(data)
+            .into_script(),
+        value: 0,
+    });
+    let change_script = options.change_script(sender.address, network)?;
+    let change_dust = fee::dust_threshold(change_script.len(), network);
+
+    let script_lens = output
+        .iter()
+        .chain(op_return.iter())
+        .map(|o| o.script_pubkey.len())
+        .collect::<Vec<_>>();
+    let fee_for = |inputs, change: bool| {
+        let mut lens = script_lens.clone();
+        if change {
+            lens.push(change_script.len());
+        }
+        options.fee.amount(
+            fee::estimate_size(inputs, sender.script_sig_len, &lens),
+            network,
+        )
+    };
+    // leftovers smaller than this are cheaper to give up than to keep
+    let cost_of_change = fee_for(1, true)
+        .saturating_sub(fee_for(1, false))
+        .saturating_add(change_dust);
+    let mut fee = fee_for(1, false);
+    let (inputs, change, fee) = loop {
+        let target = output_size
+            .checked_add(fee)
+            .ok_or(TxBuildError::Overflow { what: "output" })?;
+        let selected = select_from_sender(
+            &unique,
+            &sender.scripts[0],
+            target,
+            cost_of_change,
+            options.coin_selection,
+        )?;
+        // selection covers the target, so this only fails on overflow
+        let available = selected
+            .iter()
+            .try_fold(0u64, |acc, u| acc.checked_add(u.output.value))
+            .and_then(|total| total.checked_sub(output_size))
+            .ok_or(TxBuildError::Overflow { what: "input" })?;
+        let required = fee_for(selected.len(), false);
+        let fee_with_change = fee_for(selected.len(), true);
+        if available >= fee_with_change && available - fee_with_change >= change_dust {
+            break (selected, available - fee_with_change, fee_with_change);
+        } else if available >= required {
+            break (selected, 0, available);
+        }
+        // more inputs cost more than the selection was made for
+        fee = required;
+    };
+
+    let change_vout = if change > 0 {
+        Some(output.len() as u32)
+    } else {
+        None
+    };
+    if change > 0 {
+        output.push(TxOut {
+            script_pubkey: change_script,
+            value: change,
+        });
+    }
+    output.extend(op_return);
+    let input: Vec<TxIn> = inputs
+        .into_iter()
+        .map(|u| TxIn {
+            previous_output: u.outpoint,
+            script_sig: bitcoin::Script::new(),
+            sequence: options.sequence(),
+            witness: vec![],
+        })
+        .collect();
+    let size = fee::estimate_size(
+        input.len(),
+        sender.script_sig_len,
+        &output
+            .iter()
+            .map(|o| o.script_pubkey.len())
+            .collect::<Vec<_>>(),
+    );
+    if size > fee::MAX_STANDARD_TX_SIZE {
+        return Err(TxBuildError::TooLarge {
+            size,
+            max: fee::MAX_STANDARD_TX_SIZE,
+        }
+        .into());
+    }
+
+    Ok(BuiltTx {
+        spent: input.iter().map(|i| i.previous_output).collect(),
+        tx: Transaction {
+            version: 1,
+            lock_time: 0,
+            input,
+            output,
+        },
+        fee: Amount::from_sat(fee),
+        change: Amount::from_sat(change),
+        change_vout,
+    })
+}
+
+/// `select::select`, moving an output paying to `sender` to the front and
+/// adding the largest one if none was picked
+fn select_from_sender(
+    utxos: &[Utxo],
+    sender: &Script,
+    target: u64,
+    cost_of_change: u64,
+    strategy: CoinSelection,
+) -> Result<Vec<Utxo>, Error> {
+    let is_sender = |u: &Utxo| &u.output.script_pubkey == sender;
+    let mut selected = select::select(utxos.to_vec(), target, cost_of_change, strategy)?;
+    if !selected.iter().any(is_sender) {
+        let first = utxos
+            .iter()
+            .filter(|u| is_sender(u))
+            .max_by_key(|u| u.output.value)
+            .ok_or(TxBuildError::NoMatchingInputs {
+                reason: "the sender has no outputs to spend".to_owned(),
+            })?;
+        let rest = utxos
+            .iter()
+            .filter(|u| u.outpoint != first.outpoint)
+            .cloned()
+            .collect();
+        selected = select::select(
+            rest,
+            target.saturating_sub(first.output.value),
+            cost_of_change,
+            strategy,
+        )?;
+        selected.insert(0, first.clone());
+    }
+    // stable, so the order is otherwise kept
+    selected.sort_by_key(|u| !is_sender(u));
+    Ok(selected)
+}
+
+pub fn sign_p2pkh(
+    tx: &mut Transaction,
+    script: &Script,
+    mpriv: &SecretKey,
+    pubkey: &[u8],
+) -> Result<(), Error> {
+    let inputs = (0..tx.input.len()).collect::<Vec<_>>();
+    sign_p2pkh_inputs(tx, &inputs, script, mpriv, pubkey)
+}
+
+/// signs the inputs of `tx` at the positions `inputs`, all spending `script`
+pub fn sign_p2pkh_inputs(
+    tx: &mut Transaction,
+    inputs: &[usize],
+    script: &Script,
+    mpriv: &SecretKey,
+    pubkey: &[u8],
+) -> Result<(), Error> {
+    use bitcoin_hashes::Hash;
+
+    let script_sigs = inputs
+        .iter()
+        .map(|&i| {
+            ensure!(i < tx.input.len(), "no input {} to sign", i);
+            let sighash = tx.signature_hash(i, script, 0x01).into_inner();
+            let (sig, _) = secp256k1::sign(&secp256k1::Message::parse(&sighash), mpriv);
+            let sig_der = sig.serialize_der();
+            Ok(bitcoin::Script::from(
+                [
+                    &[sig_der.as_ref().len() as u8 + 1][..],
+                    sig_der.as_ref(),
+                    &[0x01, pubkey.len() as u8][..],
+                    pubkey,
+                ]
+                .concat(),
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    for (&i, script_sig) in inputs.iter().zip(script_sigs) {
+        tx.input[i].script_sig = script_sig;
+    }
+    Ok(())
+}
+
+/// signatures already present in a partially signed p2sh multisig scriptSig,
+/// i.e. every push between the leading OP_0 and the trailing redeem script
+pub fn multisig_signatures(script_sig: &Script) -> Vec<Vec<u8>> {
+    use bitcoin::blockdata::script::Instruction;
+
+    let mut pushes = script_sig
+        .iter(true)
+        .filter_map(|i| match i {
+            Instruction::PushBytes(b) => Some(b.to_vec()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if pushes.len() < 2 {
+        return Vec::new();
+    }
+    pushes.pop();
+    pushes.remove(0);
+    pushes
+}
+
+/// matches DER signatures (with sighash byte) to `pubkeys` in order, the way
+/// OP_CHECKMULTISIG consumes them
+pub fn match_multisig_signatures(
+    tx: &Transaction,
+    input: usize,
+    redeem_script: &Script,
+    pubkeys: &[PublicKey],
+    sigs: &[Vec<u8>],
+) -> Option<Vec<Option<Vec<u8>>>> {
+    use bitcoin_hashes::Hash;
+
+    let mut res = vec![None; pubkeys.len()];
+    let mut next_key = 0;
+    for sig in sigs {
+        let (sighash_type, der) = sig.split_last()?;
+        let msg = secp256k1::Message::parse(
+            &tx.signature_hash(input, redeem_script, *sighash_type as u32)
+                .into_inner(),
+        );
+        let parsed = secp256k1::Signature::parse_der_lax(der).ok()?;
+        let found =
+            (next_key..pubkeys.len()).find(|k| secp256k1::verify(&msg, &parsed, &pubkeys[*k]))?;
+        res[found] = Some(sig.clone());
+        next_key = found + 1;
+    }
+    Some(res)
+}
+
+/// adds a signature by `mpriv` to every input spending the p2sh multisig
+/// `redeem_script`, keeping existing signatures in key order
+pub fn sign_p2sh_multisig(
+    tx: &mut Transaction,
+    redeem_script: &Script,
+    pubkeys: &[PublicKey],
+    threshold: usize,
+    mpriv: &SecretKey,
+) -> Result<(), Error> {
+    use bitcoin::blockdata::script::Builder;
+    use bitcoin_hashes::Hash;
+
+    let own = PublicKey::from_secret_key(mpriv);
+    let position = pubkeys
+        .iter()
+        .position(|k| k == &own)
+        .ok_or(format_err!("key is not a cosigner"))?;
+    let script_sigs = (0..tx.input.len())
+        .map(|i| {
+            let existing = multisig_signatures(&tx.input[i].script_sig);
+            let mut slots = match_multisig_signatures(tx, i, redeem_script, pubkeys, &existing)
+                .ok_or(format_err!("invalid signature in input {}", i))?;
+            if slots[position].is_none() {
+                ensure!(
+                    existing.len() < threshold,
+                    "input {} is already fully signed",
+                    i
+                );
+                let sighash = tx.signature_hash(i, redeem_script, 0x01).into_inner();
+                let (sig, _) = secp256k1::sign(&secp256k1::Message::parse(&sighash), mpriv);
+                let mut sig_der = sig.serialize_der().as_ref().to_vec();
+                sig_der.push(0x01);
+                slots[position] = Some(sig_der);
+            }
+            Ok(slots
+                .iter()
+                .flatten()
+                .fold(Builder::new().push_slice(&[]), |b, sig| b.push_slice(sig))
+                .push_slice(redeem_script.as_bytes())
+                .into_script())
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    for (vin, script_sig) in tx.input.iter_mut().zip(script_sigs) {
+        vin.script_sig = script_sig;
+    }
+    Ok(())
+}
+
+pub fn serialize(tx: &Transaction) -> Result<Vec<u8>, Error> {
+    use bitcoin::consensus::Encodable;
+
+    let mut res = Vec::new();
+    tx.consensus_encode(&mut res)?;
+    Ok(res)
+}
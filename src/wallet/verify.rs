@@ -0,0 +1,62 @@
+//! Checking signed transactions the way nodes and `parse_tx` will, before they
+//! leave the wallet, so a derivation or sighash bug is an error here rather
+//! than a rejection after broadcast.
+
+use super::script_to_addr;
+use super::tx::{BuiltTx, Utxo};
+use crate::protocol;
+use crate::Network;
+use bitcoin::{Script, Transaction};
+use chrono::Utc;
+use failure::Error;
+
+/// checks the scriptSig of every input of `tx` verifies against the output it
+/// spends, found in `utxos`
+pub fn verify_inputs(tx: &Transaction, utxos: &[Utxo], network: Network) -> Result<(), Error> {
+    for (i, input) in tx.input.iter().enumerate() {
+        let prevout = utxos
+            .iter()
+            .find(|u| u.outpoint == input.previous_output)
+            .ok_or(format_err!("input {} spends an unknown output", i))?;
+        verify_input(tx, i, &prevout.output.script_pubkey, network)?;
+    }
+    Ok(())
+}
+
+/// checks the scriptSig of input `i` of `tx` verifies against `script`, the
+/// script of the output it spends
+pub fn verify_input(
+    tx: &Transaction,
+    i: usize,
+    script: &Script,
+    network: Network,
+) -> Result<(), Error> {
+    let expected = script_to_addr(script, network)?;
+    let signer = protocol::p2pkh_signer(tx, i, network)
+        .or_else(|| protocol::p2sh_multisig_signer(tx, i, network));
+    ensure!(
+        signer.as_ref() == Some(&expected),
+        "input {} of {} does not verify against {}",
+        i,
+        tx.txid(),
+        expected
+    );
+    Ok(())
+}
+
+/// checks every transaction of `chain` decodes as a bork from `sender`
+pub fn verify_borks(chain: &[BuiltTx], sender: &str, network: Network) -> Result<(), Error> {
+    let time = Utc::now();
+    for built in chain {
+        let bork = protocol::parse_tx(built.tx.clone(), &time, network)
+            .ok_or(format_err!("{} does not decode as a bork", built.txid()))?;
+        ensure!(
+            bork.sender_address() == sender,
+            "{} decodes as a bork from {} rather than {}",
+            built.txid(),
+            bork.sender_address(),
+            sender
+        );
+    }
+    Ok(())
+}
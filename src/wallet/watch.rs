@@ -0,0 +1,260 @@
+use super::amount::Amount;
+use super::fee::{self, Fee};
+use super::hash160;
+use super::path::HARDENED_OFFSET;
+use super::pubkey_hash_to_addr;
+use super::tx::{self, BuiltTx, TxOptions, Utxo};
+use super::DerivationPath;
+use super::HmacSha512;
+use crate::Network;
+use failure::Error;
+use hmac::Mac;
+use secp256k1::{PublicKey, PublicKeyFormat, SecretKey};
+use std::collections::BTreeMap;
+
+/// extended public key version bytes: xpub, dgub, Ltub
+pub const XPUB_VERSIONS: [[u8; 4]; 3] = [
+    [0x04, 0x88, 0xB2, 0x1E],
+    [0x02, 0xFA, 0xCA, 0xFD],
+    [0x01, 0x9D, 0xA4, 0x62],
+];
+
+/// A wallet holding only a public key and chain code. It can derive
+/// non-hardened children, produce addresses and build unsigned
+/// transactions, but can never sign.
+#[derive(Clone)]
+pub struct WatchOnlyWallet {
+    mpub: PublicKey,
+    chain_code: [u8; 32],
+    children: BTreeMap<u32, WatchOnlyWallet>,
+}
+impl WatchOnlyWallet {
+    pub fn from_parts(mpub: PublicKey, chain_code: &[u8]) -> Result<Self, Error> {
+        ensure!(chain_code.len() == 32, "chain code must be 32 bytes");
+        let mut cc: [u8; 32] = [0; 32];
+        cc.clone_from_slice(chain_code);
+        Ok(WatchOnlyWallet {
+            mpub,
+            chain_code: cc,
+            children: BTreeMap::new(),
+        })
+    }
+
+    pub fn from_public_key(pubkey: &[u8], chain_code: &[u8]) -> Result<Self, Error> {
+        let mpub = PublicKey::parse_slice(pubkey, None).map_err(|e| format_err!("{:?}", e))?;
+        Self::from_parts(mpub, chain_code)
+    }
+
+    pub fn from_xpub(xpub: &str) -> Result<Self, Error> {
+        let data = bitcoin::util::base58::from_check(xpub)?;
+        ensure!(data.len() == 78, "invalid extended key length");
+        if !XPUB_VERSIONS.iter().any(|v| v == &data[0..4]) {
+            bail!(
+                "extended key version {} is not public",
+                hex::encode(&data[0..4])
+            );
+        }
+        let mpub = PublicKey::parse_slice(&data[45..78], Some(PublicKeyFormat::Compressed))
+            .map_err(|e| format_err!("{:?}", e))?;
+        Self::from_parts(mpub, &data[13..45])
+    }
+
+    pub fn mpub(&self) -> &PublicKey {
+        &self.mpub
+    }
+
+    pub fn chain_code(&self) -> &[u8] {
+        &self.chain_code
+    }
+
+    pub fn next_child(&mut self) -> Result<&mut WatchOnlyWallet, Error> {
+        let next = self.children.keys().next_back().map(|i| i + 1).unwrap_or(0);
+        self.load_child(next, false)
+    }
+
+    pub fn load_child(&mut self, i: u32, hardened: bool) -> Result<&mut WatchOnlyWallet, Error> {
+        if hardened {
+            bail!("cannot derive hardened child from public key");
+        }
+        ensure!(i < HARDENED_OFFSET, "child index {} out of range", i);
+
+        if !self.children.contains_key(&i) {
+            let mut mac =
+                HmacSha512::new_varkey(self.chain_code()).map_err(|e| format_err!("{}", e))?;
+            let mut v = self.mpub().serialize_compressed().to_vec();
+            v.extend(&i.to_be_bytes());
+            mac.input(&v);
+            let l = mac.result().code();
+            let ll = SecretKey::parse_slice(&l[0..32]).map_err(|e| format_err!("{:?}", e))?;
+            let mut cpub = self.mpub().clone();
+            cpub.tweak_add_assign(&ll)
+                .map_err(|e| format_err!("{:?}", e))?;
+            self.children
+                .insert(i, WatchOnlyWallet::from_parts(cpub, &l[32..64])?);
+        }
+
+        Ok(self.children.get_mut(&i).unwrap())
+    }
+
+    pub fn derive(&mut self, path: &DerivationPath) -> Result<&mut WatchOnlyWallet, Error> {
+        let mut cur = self;
+        for c in path.iter() {
+            cur = cur.load_child(c.index(), c.is_hardened())?;
+        }
+        Ok(cur)
+    }
+
+    pub fn get_child(&self, i: u32) -> Option<&WatchOnlyWallet> {
+        self.children.get(&i)
+    }
+
+    pub fn pubkey_hash(&self) -> Vec<u8> {
+        hash160(&self.mpub().serialize_compressed())
+    }
+
+    pub fn address(&self, network: Network) -> String {
+        pubkey_hash_to_addr(&self.pubkey_hash(), network)
+    }
+
+    pub fn script(&self) -> bitcoin::Script {
+        super::addr_to_script(&self.address(Network::Bitcoin), Network::Bitcoin).unwrap()
+    }
+
+    /// the size of the scriptSig spending an output of `script`, for fee estimates
+    pub fn script_sig_len(&self) -> usize {
+        fee::p2pkh_script_sig_len(33)
+    }
+
+    pub fn construct_unsigned(
+        &self,
+        inputs: &[Vec<u8>],
+        outputs: &[(&str, Amount)],
+        fee: Amount,
+        op_return: Option<&[u8]>,
+        network: Network,
+    ) -> Result<Vec<u8>, Error> {
+        self.build_unsigned(
+            &Utxo::from_txs(inputs, &self.script())?,
+            outputs,
+            op_return,
+            &TxOptions::new(Fee::Flat(fee.as_sat())),
+            network,
+        )?
+        .serialize()
+    }
+
+    /// like `construct_unsigned`, also reporting the outputs spent
+    pub fn build_unsigned(
+        &self,
+        utxos: &[Utxo],
+        outputs: &[(&str, Amount)],
+        op_return: Option<&[u8]>,
+        options: &TxOptions,
+        network: Network,
+    ) -> Result<BuiltTx, Error> {
+        tx::construct_p2pkh_unsigned(
+            &self.mpub().serialize_compressed(),
+            utxos,
+            outputs,
+            op_return,
+            options,
+            network,
+        )
+    }
+
+    fn serializable(&self) -> SerializableWatchOnlyWallet {
+        SerializableWatchOnlyWallet {
+            mpub: self.mpub().serialize_compressed().to_vec(),
+            chain_code: self.chain_code.to_vec(),
+        }
+    }
+
+    pub fn as_bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(serde_cbor::ser::to_vec_packed(&self.serializable())?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let w: SerializableWatchOnlyWallet = serde_cbor::from_slice(bytes)?;
+        Self::from_public_key(&w.mpub, &w.chain_code)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SerializableWatchOnlyWallet {
+    #[serde(with = "serde_bytes")]
+    mpub: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    chain_code: Vec<u8>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::ChildWallet;
+
+    /// BIP32 test vector 1
+    const MASTER: &str = "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508";
+    const XPUB_0H: &str = "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw";
+    const XPUB_0H_1: &str = "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ";
+
+    fn master() -> ChildWallet {
+        let mut seed = [0; 64];
+        seed.copy_from_slice(&hex::decode(MASTER).unwrap());
+        ChildWallet::new(seed)
+    }
+
+    #[test]
+    fn public_derivation_matches_private() {
+        let mut master = master();
+        let account = master.derive(&"m/0'".parse().unwrap()).unwrap();
+        let mut watch = account.watch_only();
+        for path in ["m/1", "m/1/7/3", "m/2147483647"].iter() {
+            let path: DerivationPath = path.parse().unwrap();
+            assert_eq!(
+                watch.derive(&path).unwrap().address(Network::Dogecoin),
+                account.derive(&path).unwrap().address(Network::Dogecoin)
+            );
+        }
+        assert_eq!(
+            watch
+                .derive(&"m/1/7/3".parse().unwrap())
+                .unwrap()
+                .address(Network::Dogecoin),
+            "D8HmJy3WgDeCBb394LBszfimSezYe2Ag8M"
+        );
+        assert!(watch.load_child(0, true).is_err());
+    }
+
+    #[test]
+    fn from_xpub() {
+        let mut watch = WatchOnlyWallet::from_xpub(XPUB_0H).unwrap();
+        let mut account = master();
+        let account = account.derive(&"m/0'".parse().unwrap()).unwrap();
+        assert_eq!(watch.mpub(), account.mpub());
+        assert_eq!(watch.chain_code(), account.chain_code());
+
+        let child = WatchOnlyWallet::from_xpub(XPUB_0H_1).unwrap();
+        let derived = watch.load_child(1, false).unwrap();
+        assert_eq!(derived.mpub(), child.mpub());
+        assert_eq!(derived.chain_code(), child.chain_code());
+    }
+
+    #[test]
+    fn from_parts_checks_chain_code() {
+        let master = master();
+        assert!(WatchOnlyWallet::from_parts(master.mpub().clone(), &[0; 31]).is_err());
+        assert!(WatchOnlyWallet::from_public_key(
+            &master.mpub().serialize_compressed(),
+            &master.chain_code()[1..]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let watch = master().watch_only();
+        let restored = WatchOnlyWallet::from_bytes(&watch.as_bytes().unwrap()).unwrap();
+        assert_eq!(restored.mpub(), watch.mpub());
+        assert_eq!(restored.chain_code(), watch.chain_code());
+    }
+}
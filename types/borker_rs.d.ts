@@ -1,6 +1,20 @@
 /* tslint:disable */
 export function processBlock(block: string, network: Network): BorkTxData[];
 
+export function verifyMessage(address: string, signature: string, message: string, network: Network): boolean;
+
+export function combinePsbts(psbts: string[]): string;
+
+export function finalizePsbt(psbt: string): string;
+
+export function extractPsbt(psbt: string): string;
+
+export function encodeBork(data: NewBorkData, nonce: number, version?: number, network?: Network): string[];
+
+export function sweepKeys(keys: string[], inputs: Input[], destination: string, fee: Amount | Fee, network: Network): BuiltTx;
+
+export function inspectTx(tx: string, inputs: Input[] | null, time: string, network: Network): TxInfo;
+
 export enum BorkType {
   SetName = 'set_name',
   SetBio = 'set_bio',
@@ -40,9 +54,137 @@ export interface NewBorkData {
   referenceId?: string | null,
 }
 
+/**
+ * Set as `txBuildError` on errors thrown when a transaction cannot be built.
+ * `network` is left out of `invalidAddress`: it is the one asked for.
+ */
+export type TxBuildError =
+  | { kind: 'insufficientFunds', needed: number, available: number }
+  | { kind: 'noMatchingInputs', reason: string }
+  | { kind: 'foreignInput', txid: string, vout: number }
+  | { kind: 'dustOutput', index: number, value: number, threshold: number }
+  | { kind: 'opReturnTooLarge', len: number, max: number }
+  | { kind: 'tooLarge', size: number, max: number }
+  | { kind: 'invalidAddress', address: string, reason: string }
+  | { kind: 'chainedChange', txs: number }
+  | { kind: 'overflow', what: string };
+
+/** koinu, or a string of coins such as "12.5" or "12.5 DOGE" */
+export type Amount = number | string;
+
 export interface Output {
   address: string,
+  value: Amount,
+}
+
+export interface Utxo {
+  txid: string,
+  vout: number,
+  value: Amount,
+  script: string,
+  tx?: string,
+}
+
+/** a hex funding transaction or an unspent output */
+export type Input = string | Utxo;
+
+export enum CoinSelection {
+  All = 'all',
+  LargestFirst = 'largestFirst',
+  BranchAndBound = 'branchAndBound',
+}
+
+export type Fee =
+  | { flat: number }
+  | { perByte: number }
+  | { perKb: number };
+
+export type ChangePolicy = 'sender' | { address: string };
+
+export interface TxOptions {
+  fee: Fee,
+  coinSelection?: CoinSelection,
+  change?: ChangePolicy,
+  rbf?: boolean,
+  dataCarrierSize?: number,
+}
+
+export interface OutPoint {
+  txid: string,
+  vout: number,
+}
+
+export interface BuiltTx {
+  tx: string,
+  txid: string,
+  spent: OutPoint[],
+  fee: number,
+  change: number,
+  changeVout: number | null,
+}
+
+export interface PlannedTx {
+  fee: number,
+  change: number,
+  payloadLen: number,
+}
+
+export interface BorkPlan {
+  txs: PlannedTx[],
+  fee: number,
+  change: number,
+}
+
+export enum OutputType {
+  P2pkh = 'p2pkh',
+  P2sh = 'p2sh',
+  OpReturn = 'opReturn',
+  Nonstandard = 'nonstandard',
+}
+
+export interface InputInfo {
+  txid: string,
+  vout: number,
+  sequence: number,
+  value: number | null,
+  address: string | null,
+  signed: boolean,
+}
+
+export interface OutputInfo {
   value: number,
+  type: OutputType,
+  address: string | null,
+  data: string | null,
+}
+
+export interface TxInfo {
+  txid: string,
+  size: number,
+  inputs: InputInfo[],
+  outputs: OutputInfo[],
+  fee: number | null,
+  /** koinu per byte */
+  feeRate: number | null,
+  bork: BorkTxData | null,
+  signed: boolean,
+}
+
+export interface DiscoveredAddress {
+  path: string,
+  address: string,
+}
+
+export interface ChainDiscovery {
+  used: DiscoveredAddress[],
+  nextUnused: DiscoveredAddress,
+}
+
+export interface AccountDiscovery {
+  account: number,
+  path: string,
+  external: ChainDiscovery,
+  change: ChainDiscovery,
 }
 
 export class JsWallet {
@@ -55,10 +197,53 @@ export class JsWallet {
 
   childAt(derivationPath: number[]): JsChildWallet;
 
+  childAtPath(derivationPath: string): JsChildWallet;
+
+  discover(network: Network, seen: string[], gapLimit?: number): AccountDiscovery[];
+
+  buildSigned(
+    sender: string,
+    paths: string[],
+    inputs: Input[],
+    outputs: Output[],
+    opReturn: string | null,
+    options: TxOptions,
+    network: Network,
+  ): BuiltTx;
+
+  newBork(
+    sender: string,
+    paths: string[],
+    data: NewBorkData,
+    inputs: Input[],
+    recipient: Output | null,
+    mentions: Output[],
+    fee: Amount | Fee,
+    network: Network,
+    version?: number,
+    rbf?: boolean
+  ): string[];
+
+  sweep(
+    paths: string[],
+    inputs: Input[],
+    destination: string,
+    fee: Amount | Fee,
+    network: Network,
+  ): BuiltTx;
+
   toBuffer(): string;
 
   static fromBuffer(buf: string): JsWallet;
 
+  toEncryptedBuffer(password: string): string;
+
+  static fromEncryptedBuffer(buf: string, password: string): JsWallet;
+
+  static isEncryptedBuffer(buf: string): boolean;
+
+  static encryptBuffer(buf: string, password: string): string;
+
 }
 
 export enum Network {
@@ -75,19 +260,258 @@ export class JsChildWallet {
 
   newBork(
     data: NewBorkData,
-    inputs: string[],
+    inputs: Input[],
+    recipient: Output | null,
+    mentions: Output[],
+    fee: Amount | Fee,
+    network: Network,
+    version?: number,
+    rbf?: boolean
+  ): string[];
+
+  planBork(
+    data: NewBorkData,
+    inputs: Input[],
     recipient: Output | null,
     mentions: Output[],
-    fee: number,
+    fee: Amount | Fee,
     network: Network,
     version?: number
+  ): BorkPlan;
+
+  constructSigned(
+    inputs: Input[],
+    destination: string,
+    amount: Amount,
+    fee: Amount | Fee,
+    network: Network,
+  ): string;
+
+  buildSigned(
+    inputs: Input[],
+    outputs: Output[],
+    opReturn: string | null,
+    options: TxOptions,
+    network: Network,
+  ): BuiltTx;
+
+  bumpFee(
+    chain: string[],
+    inputs: Input[],
+    options: TxOptions,
+    network: Network,
+  ): BuiltTx[];
+
+  cpfp(
+    parent: string,
+    parentFee: Amount,
+    options: TxOptions,
+    network: Network,
+  ): BuiltTx;
+
+  toWif(network: Network): string;
+
+  watchOnly(): JsWatchOnlyWallet;
+
+  syncNonce(observed: number[]): void;
+
+  signMessage(message: string, network: Network): string;
+
+  createPsbt(
+    inputs: string[],
+    outputs: Output[],
+    fee: Amount | Fee,
+    opReturn: string | null,
+    network: Network,
+  ): string;
+
+  signPsbt(psbt: string): string;
+
+}
+
+export class JsKeyWallet {
+
+  free(): void;
+
+  static fromWif(wif: string, network: Network): JsKeyWallet;
+
+  toWif(network: Network): string;
+
+  address(network: Network): string;
+
+  syncNonce(observed: number[]): void;
+
+  signMessage(message: string, network: Network): string;
+
+  newBork(
+    data: NewBorkData,
+    inputs: Input[],
+    recipient: Output | null,
+    mentions: Output[],
+    fee: Amount | Fee,
+    network: Network,
+    version?: number,
+    rbf?: boolean
   ): string[];
 
+  planBork(
+    data: NewBorkData,
+    inputs: Input[],
+    recipient: Output | null,
+    mentions: Output[],
+    fee: Amount | Fee,
+    network: Network,
+    version?: number
+  ): BorkPlan;
+
   constructSigned(
+    inputs: Input[],
+    destination: string,
+    amount: Amount,
+    fee: Amount | Fee,
+    network: Network,
+  ): string;
+
+  buildSigned(
+    inputs: Input[],
+    outputs: Output[],
+    opReturn: string | null,
+    options: TxOptions,
+    network: Network,
+  ): BuiltTx;
+
+  bumpFee(
+    chain: string[],
+    inputs: Input[],
+    options: TxOptions,
+    network: Network,
+  ): BuiltTx[];
+
+  cpfp(
+    parent: string,
+    parentFee: Amount,
+    options: TxOptions,
+    network: Network,
+  ): BuiltTx;
+
+  createPsbt(
     inputs: string[],
+    outputs: Output[],
+    fee: Amount | Fee,
+    opReturn: string | null,
+    network: Network,
+  ): string;
+
+  signPsbt(psbt: string): string;
+
+}
+
+export class JsWatchOnlyWallet {
+
+  free(): void;
+
+  static fromXpub(xpub: string): JsWatchOnlyWallet;
+
+  static fromPublicKey(pubkey: string, chainCode: string): JsWatchOnlyWallet;
+
+  publicKey(): string;
+
+  chainCode(): string;
+
+  childAt(derivationPath: number[]): JsWatchOnlyWallet;
+
+  childAtPath(derivationPath: string): JsWatchOnlyWallet;
+
+  address(network: Network): string;
+
+  constructUnsigned(
+    inputs: Input[],
+    destination: string,
+    amount: Amount,
+    fee: Amount | Fee,
+    network: Network,
+  ): string;
+
+  buildUnsigned(
+    inputs: Input[],
+    outputs: Output[],
+    opReturn: string | null,
+    options: TxOptions,
+    network: Network,
+  ): BuiltTx;
+
+  toBuffer(): string;
+
+  static fromBuffer(buf: string): JsWatchOnlyWallet;
+
+  createPsbt(
+    inputs: string[],
+    outputs: Output[],
+    fee: Amount | Fee,
+    opReturn: string | null,
+    network: Network,
+  ): string;
+
+}
+
+export class JsMultisigWallet {
+
+  free(): void;
+
+  constructor(threshold: number, publicKeys: string[]);
+
+  static fromRedeemScript(redeemScript: string): JsMultisigWallet;
+
+  redeemScript(): string;
+
+  threshold(): number;
+
+  address(network: Network): string;
+
+  newBork(
+    signer: JsChildWallet,
+    data: NewBorkData,
+    inputs: Input[],
+    recipient: Output | null,
+    mentions: Output[],
+    fee: Amount | Fee,
+    network: Network,
+    version?: number
+  ): string[];
+
+  constructSigned(
+    signer: JsChildWallet,
+    inputs: Input[],
     destination: string,
-    amount: number,
-    fee: number,
+    amount: Amount,
+    fee: Amount | Fee,
+    network: Network,
+  ): string;
+
+  buildSigned(
+    signer: JsChildWallet,
+    inputs: Input[],
+    outputs: Output[],
+    opReturn: string | null,
+    options: TxOptions,
+    network: Network,
+  ): BuiltTx;
+
+  sign(signer: JsChildWallet, tx: string): string;
+
+  isComplete(tx: string): boolean;
+
+  syncNonce(observed: number[]): void;
+
+  toBuffer(): string;
+
+  static fromBuffer(buf: string): JsMultisigWallet;
+
+  createPsbt(
+    inputs: string[],
+    outputs: Output[],
+    fee: Amount | Fee,
+    opReturn: string | null,
     network: Network,
   ): string;
 